@@ -4,3 +4,44 @@ use anchor_lang::prelude::*;
 pub const BRIDGE_SEED: &[u8] = b"BRIDGE_SEED";
 pub const BRIDGE_TOKEN_VAULT_SEED: &[u8] = b"BRIDGE_TOKEN_VAULT_SEED";
 pub const VAULT_SEED: &[u8] = b"VAULT_SEED";
+pub const MESSAGE_RECEIPT_SEED: &[u8] = b"MESSAGE_RECEIPT_SEED";
+pub const OUTBOUND_SEQUENCE_SEED: &[u8] = b"OUTBOUND_SEQUENCE_SEED";
+pub const OUTBOUND_MESSAGE_SEED: &[u8] = b"OUTBOUND_MESSAGE_SEED";
+pub const TOKEN_ROUTE_SEED: &[u8] = b"TOKEN_ROUTE_SEED";
+pub const WRAPPED_MINT_SEED: &[u8] = b"WRAPPED_MINT_SEED";
+pub const SOL_ESCROW_SEED: &[u8] = b"SOL_ESCROW_SEED";
+pub const LP_MINT_SEED: &[u8] = b"LP_MINT_SEED";
+pub const LP_POSITION_SEED: &[u8] = b"LP_POSITION_SEED";
+pub const FEE_SCHEDULE_SEED: &[u8] = b"FEE_SCHEDULE_SEED";
+pub const FEE_COLLECTOR_SEED: &[u8] = b"FEE_COLLECTOR_SEED";
+pub const REFERRAL_SEED: &[u8] = b"REFERRAL_SEED";
+pub const ROLE_SEED: &[u8] = b"ROLE_SEED";
+pub const QUEUED_OPERATION_SEED: &[u8] = b"QUEUED_OPERATION_SEED";
+
+// size of the Bridge account. Bridges created with the legacy 5000 byte layout must be
+// resized by migrate_bridge_layout, which is how every other instruction tells them apart
+pub const BRIDGE_SPACE: usize = 6 * 1024;
+
+pub const MAX_GUARDIANS: usize = 19;
+pub const MAX_FEE_RECIPIENTS: usize = 8;
+
+// keeps every 10^decimals scale factor inside u128
+pub const MAX_TOKEN_DECIMALS: u8 = 36;
+// wrapped mints cap their decimals so 18 decimal EVM amounts fit in u64
+pub const MAX_WRAPPED_DECIMALS: u8 = 8;
+
+// token id schemes, see Bridge::get_evm_token_id
+pub const TOKEN_ID_VERSION_LEGACY: u8 = 0;
+pub const TOKEN_ID_VERSION_EVM: u8 = 1;
+
+// liquidity and token fees are charged in basis points of the bridged amount
+pub const BPS_DENOMINATOR: u64 = 10_000;
+pub const MAX_LP_FEE_BPS: u16 = 1_000;
+pub const MAX_TOKEN_FEE_BPS: u16 = 1_000;
+// scale of TokenRoute::acc_fee_per_share
+pub const LP_FEE_PRECISION: u128 = 1_000_000_000_000;
+
+// bounds for Bridge::timelock_delay, in seconds. The lower one leaves guardians
+// time to cancel a queued operation
+pub const MIN_TIMELOCK_DELAY: i64 = 24 * 60 * 60;
+pub const MAX_TIMELOCK_DELAY: i64 = 30 * 24 * 60 * 60;
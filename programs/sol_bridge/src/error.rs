@@ -23,5 +23,67 @@ pub enum BridgeErrorCode {
     #[msg("The target balance is overflow.")]
     Overflow,
     #[msg("The target balance is underflow.")]
-    Underflow
+    Underflow,
+    #[msg("Invalid guardian set.")]
+    InvalidGuardianSet,
+    #[msg("Invalid guardian signature instruction.")]
+    InvalidGuardianSignature,
+    #[msg("Not enough guardian signatures.")]
+    InsufficientGuardianSignatures,
+    #[msg("The message is already processed.")]
+    MessageAlreadyProcessed,
+    #[msg("Invalid recipient.")]
+    InvalidRecipient,
+    #[msg("Invalid remote token address.")]
+    InvalidRemoteToken,
+    #[msg("Invalid token id version.")]
+    InvalidTokenIdVersion,
+    #[msg("The operation is not supported by the route mode.")]
+    InvalidRouteMode,
+    #[msg("The bridge must be the mint authority.")]
+    InvalidMintAuthority,
+    #[msg("The bridge token account is required.")]
+    MissingBridgeTokenAccount,
+    #[msg("The mint has an unsupported extension.")]
+    UnsupportedMintExtension,
+    #[msg("An account required by the route mode is missing.")]
+    MissingRouteAccount,
+    #[msg("Invalid token decimals.")]
+    InvalidDecimals,
+    #[msg("The amount is too small to bridge.")]
+    AmountTooSmall,
+    #[msg("Invalid liquidity fee.")]
+    InvalidLpFee,
+    #[msg("No liquidity fees to claim.")]
+    NoLpFees,
+    #[msg("Invalid fee schedule.")]
+    InvalidFeeSchedule,
+    #[msg("The fee collection token account is required.")]
+    MissingFeeTokenAccount,
+    #[msg("The fees exceed the limits set by the sender.")]
+    FeeLimitExceeded,
+    #[msg("Invalid fee recipients.")]
+    InvalidFeeRecipients,
+    #[msg("Invalid referrer fee.")]
+    InvalidReferrerFee,
+    #[msg("Invalid pending owner.")]
+    InvalidPendingOwner,
+    #[msg("The bridge is paused.")]
+    BridgePaused,
+    #[msg("Sending on this route is paused.")]
+    SendPaused,
+    #[msg("Receiving on this route is paused.")]
+    ReceivePaused,
+    #[msg("Invalid timelock delay.")]
+    InvalidTimelockDelay,
+    #[msg("The operation's timelock has not expired.")]
+    TimelockNotExpired,
+    #[msg("The queued operation does not match this instruction.")]
+    InvalidOperation,
+    #[msg("The signer is not a guardian.")]
+    InvalidGuardian,
+    #[msg("The route still holds tokens or liquidity.")]
+    RouteNotEmpty,
+    #[msg("The bridge account does not have the expected layout.")]
+    InvalidBridgeLayout
 }
@@ -1,11 +1,35 @@
 use anchor_lang::prelude::*;
 
+use crate::state::{FeeRecipient, FeeScheduleParams, Operation, Role, RouteMode};
+
 #[event]
 pub struct AddLiquidityEvent {
+    pub provider: Pubkey,
+    pub local_token: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub remote_chain_selector: u64,
+    pub remote_token: [u8; 20],
+}
+
+#[event]
+pub struct RemoveLiquidityEvent {
+    pub provider: Pubkey,
+    pub local_token: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub remote_chain_selector: u64,
+    pub remote_token: [u8; 20],
+}
+
+#[event]
+pub struct ClaimLpFeesEvent {
+    pub provider: Pubkey,
     pub local_token: Pubkey,
     pub amount: u64,
+    pub shares: u64,
     pub remote_chain_selector: u64,
-    pub remote_token: String,
+    pub remote_token: [u8; 20],
 }
 
 #[event]
@@ -14,15 +38,69 @@ pub struct SendTokenEvent {
     pub amount: u64,
     pub remote_bridge: String,
     pub remote_chain_selector: u64,
-    pub remote_token: String,
+    pub remote_token: [u8; 20],
+    pub recipient: [u8; 20],
+    pub remote_amount: u128,
+    pub lamport_fee: u64,
+    pub token_fee: u64,
+    pub lp_fee: u64,
+    pub referrer: Option<Pubkey>,
+    pub referral_fee: u64,
+    pub nonce: u64,
+    pub outbound_message: Pubkey,
 }
 
 #[event]
 pub struct MessageReceivedEvent {
     pub source_chain_selector: u64,
+    pub message_id: [u8; 32],
     pub to_address: Pubkey,
-    pub token_id: String,
+    pub token_id: [u8; 32],
     pub amount: u64,
+    pub remote_amount: u128,
+    pub lp_fee: u64,
+}
+
+#[event]
+pub struct ProposeOwnerEvent {
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+}
+
+#[event]
+pub struct AcceptOwnerEvent {
+    pub previous_owner: Pubkey,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct CancelOwnerProposalEvent {
+    pub pending_owner: Pubkey,
+    pub cancelled_by: Pubkey,
+}
+
+#[event]
+pub struct GrantRoleEvent {
+    pub role: Role,
+    pub member: Pubkey,
+}
+
+#[event]
+pub struct RevokeRoleEvent {
+    pub role: Role,
+    pub member: Pubkey,
+}
+
+#[event]
+pub struct SetPausedEvent {
+    pub paused: bool,
+}
+
+#[event]
+pub struct SetRoutePausedEvent {
+    pub token_id: [u8; 32],
+    pub send_paused: bool,
+    pub receive_paused: bool,
 }
 
 #[event]
@@ -30,23 +108,127 @@ pub struct WithdrawEvent {
     pub beneficiary: Pubkey,
 }
 
+#[event]
+pub struct SetFeeRecipientsEvent {
+    pub fee_recipients: Vec<FeeRecipient>,
+}
+
+#[event]
+pub struct DistributeFeesEvent {
+    pub recipients: Vec<Pubkey>,
+    pub amounts: Vec<u64>,
+}
+
+#[event]
+pub struct ClaimReferralFeesEvent {
+    pub referrer: Pubkey,
+    pub amount: u64,
+}
+
 #[event]
 pub struct WithdrawTokenEvent {
     pub token: Pubkey,
     pub amount: u64,
 }
 
+#[event]
+pub struct WithdrawTokenFeesEvent {
+    pub token: Pubkey,
+    pub amount: u64,
+}
+
 
 #[event]
 pub struct AddTokenEvent {
     pub local_token: Pubkey,
     pub remote_chain_selector: u64,
-    pub remote_token: String,
-    pub token_id: String,
+    pub remote_token: [u8; 20],
+    pub token_id: [u8; 32],
+    pub mode: RouteMode,
+}
+
+#[event]
+pub struct CreateWrappedTokenEvent {
+    pub local_token: Pubkey,
+    pub remote_chain_selector: u64,
+    pub remote_token: [u8; 20],
+    pub decimals: u8,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
 }
 
 #[event]
 pub struct RemoveTokenEvent {
-    pub token_id: String,
+    pub token_id: [u8; 32],
     pub local_token: Pubkey
 }
+
+#[event]
+pub struct SetLpFeeEvent {
+    pub token_id: [u8; 32],
+    pub send_fee_bps: u16,
+    pub receive_fee_bps: u16,
+}
+
+#[event]
+pub struct SetFeeScheduleEvent {
+    pub token_id: [u8; 32],
+    pub remote_chain_selector: u64,
+    pub params: FeeScheduleParams,
+}
+
+#[event]
+pub struct SetPriceFeedEvent {
+    pub price_feed: Pubkey,
+    pub max_price_age: u64,
+    pub max_price_conf_bps: u16,
+}
+
+#[event]
+pub struct SetGuardiansEvent {
+    pub guardians: Vec<Pubkey>,
+    pub threshold: u8,
+    pub guardian_set_index: u32,
+}
+
+#[event]
+pub struct MigrateTokenIdEvent {
+    pub local_token: Pubkey,
+    pub old_token_id: String,
+    pub new_token_id: String,
+}
+
+#[event]
+pub struct MigrateTokenRouteEvent {
+    pub token_id: [u8; 32],
+    pub local_token: Pubkey,
+    pub token_route: Pubkey,
+    pub target_balance: u64,
+}
+
+#[event]
+pub struct SetTimelockDelayEvent {
+    pub timelock_delay: i64,
+}
+
+#[event]
+pub struct ScheduleOperationEvent {
+    pub nonce: u64,
+    pub operation: Operation,
+    pub scheduled_by: Pubkey,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ExecuteOperationEvent {
+    pub nonce: u64,
+    pub operation: Operation,
+}
+
+#[event]
+pub struct CancelOperationEvent {
+    pub nonce: u64,
+    pub operation: Operation,
+    pub guardian: Pubkey,
+}
@@ -1,58 +1,651 @@
 use anchor_lang::prelude::*;
+use anchor_spl::{
+  metadata::{self, mpl_token_metadata::types::DataV2, CreateMetadataAccountsV3, Metadata},
+  token::{spl_token::native_mint, Mint, Token},
+  token_interface::{Mint as InterfaceMint, TokenAccount, TokenInterface}
+};
+use solana_program::{program::invoke, program_option::COption, system_instruction};
 
 use crate::{state::*, constants::*, error::*, event::*};
 
 pub fn add_token(
-  ctx: Context<ManageToken>,  
+  ctx: Context<AddToken>,  
   local_token: Pubkey,        // Local token address (on Solana)
   remote_chain_selector: u64, // EVM chain selector (uint64)
-  remote_token: String
+  remote_token: [u8; 20],     // Remote token address (on EVM)
+  mode: RouteMode,
+  remote_decimals: u8         // Decimals of the remote token
 ) -> Result<()> {
-  let bridge = &mut ctx.accounts.bridge;
-  require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
-  let token_id = bridge.add_token(local_token, remote_chain_selector, remote_token.clone())?;
+  let bridge = &ctx.accounts.bridge;
+
+  TokenRoute::check_mint_extensions(&ctx.accounts.token_mint.to_account_info())?;
+
+  match mode {
+    RouteMode::LockRelease => {}
+    // Burn/mint routes need the bridge to be able to mint the wrapped token
+    RouteMode::BurnMint => require!(
+      ctx.accounts.token_mint.mint_authority == COption::Some(bridge.key()),
+      BridgeErrorCode::InvalidMintAuthority
+    ),
+    // Native SOL routes are keyed by the wrapped SOL mint
+    RouteMode::Native => {
+      require!(local_token == native_mint::ID, BridgeErrorCode::InvalidRouteMode);
+
+      // The escrow holds its rent-exempt minimum on top of the routes' target balances,
+      // so small first sends and full releases never leave it below rent
+      let sol_escrow = ctx.accounts.sol_escrow
+        .as_ref()
+        .ok_or(BridgeErrorCode::MissingRouteAccount)?;
+      let shortfall = Rent::get()?.minimum_balance(0).saturating_sub(sol_escrow.lamports());
+      if shortfall > 0 {
+        invoke(
+          &system_instruction::transfer(&ctx.accounts.authority.key(), &sol_escrow.key(), shortfall),
+          &[
+            ctx.accounts.authority.to_account_info().clone(),
+            sol_escrow.clone(),
+            ctx.accounts.system_program.to_account_info().clone(),
+          ],
+        )?;
+      }
+    }
+  }
+
+  let token_id = Bridge::get_evm_token_id(
+    bridge.chain_selector,
+    &local_token,
+    remote_chain_selector,
+    &remote_token
+  );
+
+  let token_route = &mut ctx.accounts.token_route;
+  token_route.register(
+    token_id,
+    local_token,
+    remote_chain_selector,
+    remote_token,
+    mode,
+    ctx.bumps.token_route
+  )?;
+  token_route.set_decimals(ctx.accounts.token_mint.decimals, remote_decimals)?;
 
   // Emit event
   emit!(AddTokenEvent {
     local_token,
     remote_chain_selector,
     remote_token,
-    token_id
+    token_id,
+    mode
   });
 
   Ok(())
 }
 
 
-pub fn remove_token(
-  ctx: Context<ManageToken>,
-  local_token: Pubkey,        // Local token address (on Solana)
+// Create a wrapped SPL mint for a remote token, owned by the bridge, and
+// register it as a burn/mint route
+pub fn create_wrapped_token(
+  ctx: Context<CreateWrappedToken>,
   remote_chain_selector: u64, // EVM chain selector (uint64)
-  remote_token: String
+  remote_token: [u8; 20],     // Remote token address (on EVM)
+  remote_decimals: u8,
+  name: String,
+  symbol: String,
+  uri: String                 // Metadata URI pointing to the EVM origin
 ) -> Result<()> {
-  let bridge = &mut ctx.accounts.bridge;
-  require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
-  let token_id = bridge.remove_token(local_token, remote_chain_selector, remote_token)?;
+  let accts = ctx.accounts;
+
+  let local_token = accts.token_mint.key();
+  let signer: &[&[&[u8]]] = &[&[BRIDGE_SEED, &[ctx.bumps.bridge]]];
+
+  // Attach name/symbol/uri metadata, the bridge stays update authority
+  let cpi_accounts = CreateMetadataAccountsV3 {
+    metadata: accts.metadata.to_account_info(),
+    mint: accts.token_mint.to_account_info(),
+    mint_authority: accts.bridge.to_account_info(),
+    payer: accts.authority.to_account_info(),
+    update_authority: accts.bridge.to_account_info(),
+    system_program: accts.system_program.to_account_info(),
+    rent: accts.rent.to_account_info(),
+  };
+  let cpi_context = CpiContext::new(accts.token_metadata_program.to_account_info(), cpi_accounts);
+  metadata::create_metadata_accounts_v3(
+    cpi_context.with_signer(signer),
+    DataV2 {
+      name: name.clone(),
+      symbol: symbol.clone(),
+      uri: uri.clone(),
+      seller_fee_basis_points: 0,
+      creators: None,
+      collection: None,
+      uses: None,
+    },
+    true,
+    true,
+    None
+  )?;
+
+  let token_id = Bridge::get_evm_token_id(
+    accts.bridge.chain_selector,
+    &local_token,
+    remote_chain_selector,
+    &remote_token
+  );
+
+  accts.token_route.register(
+    token_id,
+    local_token,
+    remote_chain_selector,
+    remote_token,
+    RouteMode::BurnMint,
+    ctx.bumps.token_route
+  )?;
+  accts.token_route.set_decimals(accts.token_mint.decimals, remote_decimals)?;
+
+  emit!(CreateWrappedTokenEvent {
+    local_token,
+    remote_chain_selector,
+    remote_token,
+    decimals: accts.token_mint.decimals,
+    name,
+    symbol,
+    uri
+  });
+
+  emit!(AddTokenEvent {
+    local_token,
+    remote_chain_selector,
+    remote_token,
+    token_id,
+    mode: RouteMode::BurnMint
+  });
+
+  Ok(())
+}
+
+pub fn remove_token(ctx: Context<RemoveToken>, _token_id: [u8; 32], nonce: u64) -> Result<()> {
+  let operation = ctx.accounts.queued_operation.ready()?;
+  let Operation::RemoveToken { token_id } = operation else {
+    return err!(BridgeErrorCode::InvalidOperation);
+  };
+  require!(token_id == ctx.accounts.token_route.token_id, BridgeErrorCode::InvalidOperation);
+  ctx.accounts.token_route.check_empty()?;
 
   // Emit event
   emit!(RemoveTokenEvent {
     token_id,
-    local_token
+    local_token: ctx.accounts.token_route.local_token
+  });
+
+  emit!(ExecuteOperationEvent {
+    nonce,
+    operation,
   });
 
   Ok(())
 }
 
+pub fn set_lp_fee(ctx: Context<SetLpFee>, _token_id: [u8; 32], nonce: u64) -> Result<()> {
+  let operation = ctx.accounts.queued_operation.ready()?;
+  let Operation::SetLpFee { token_id, send_fee_bps, receive_fee_bps } = operation else {
+    return err!(BridgeErrorCode::InvalidOperation);
+  };
+  require!(token_id == ctx.accounts.token_route.token_id, BridgeErrorCode::InvalidOperation);
+
+  // send_fee_bps is charged on send, receive_fee_bps on message_receive
+  ctx.accounts.token_route.set_lp_fee(send_fee_bps, receive_fee_bps)?;
+
+  emit!(SetLpFeeEvent {
+    token_id,
+    send_fee_bps,
+    receive_fee_bps
+  });
+
+  emit!(ExecuteOperationEvent {
+    nonce,
+    operation,
+  });
+
+  Ok(())
+}
+
+// Stop the requested directions of a route, others keep their state
+pub fn pause_route(
+  ctx: Context<PauseRoute>,
+  token_id: [u8; 32],
+  send: bool,                 // Pause send
+  receive: bool               // Pause message_receive
+) -> Result<()> {
+  let token_route = &mut ctx.accounts.token_route;
+  token_route.send_paused |= send;
+  token_route.receive_paused |= receive;
+
+  emit!(SetRoutePausedEvent {
+    token_id,
+    send_paused: token_route.send_paused,
+    receive_paused: token_route.receive_paused
+  });
+
+  Ok(())
+}
+
+// Only the owner can resume a route after an incident
+pub fn unpause_route(
+  ctx: Context<UnpauseRoute>,
+  token_id: [u8; 32],
+  send: bool,                 // Resume send
+  receive: bool               // Resume message_receive
+) -> Result<()> {
+  let bridge = &ctx.accounts.bridge;
+  require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+
+  let token_route = &mut ctx.accounts.token_route;
+  token_route.send_paused &= !send;
+  token_route.receive_paused &= !receive;
+
+  emit!(SetRoutePausedEvent {
+    token_id,
+    send_paused: token_route.send_paused,
+    receive_paused: token_route.receive_paused
+  });
+
+  Ok(())
+}
+
+pub fn set_fee_schedule(
+  ctx: Context<SetFeeSchedule>,
+  _token_id: [u8; 32],
+  _remote_chain_selector: u64,
+  nonce: u64
+) -> Result<()> {
+  let operation = ctx.accounts.queued_operation.ready()?;
+  let Operation::SetFeeSchedule { token_id, remote_chain_selector, params } = operation else {
+    return err!(BridgeErrorCode::InvalidOperation);
+  };
+  require!(token_id == ctx.accounts.token_route.token_id, BridgeErrorCode::InvalidOperation);
+  require!(ctx.accounts.token_route.remote_chain_selector == remote_chain_selector, BridgeErrorCode::InvalidChainSelector);
+
+  ctx.accounts.fee_schedule.set(token_id, remote_chain_selector, params, ctx.bumps.fee_schedule)?;
+
+  emit!(SetFeeScheduleEvent {
+    token_id,
+    remote_chain_selector,
+    params
+  });
+
+  emit!(ExecuteOperationEvent {
+    nonce,
+    operation,
+  });
+
+  Ok(())
+}
+
+pub fn migrate_token_ids(ctx: Context<ManageToken>) -> Result<()> {
+  let bridge = &mut ctx.accounts.bridge;
+
+  for (local_token, old_token_id, new_token_id) in bridge.migrate_token_ids()? {
+    emit!(MigrateTokenIdEvent {
+      local_token,
+      old_token_id,
+      new_token_id
+    });
+  }
+
+  Ok(())
+}
+
+// Move a legacy route into its own TokenRoute account. `target_balance` is the part of
+// the mint's vault balance locked for this route, every other route of the mint must be
+// passed in remaining accounts so their balances are not handed out twice.
+pub fn migrate_token_route(
+  ctx: Context<MigrateTokenRoute>,
+  token_id: [u8; 32],
+  remote_decimals: u8,
+  target_balance: u64
+) -> Result<()> {
+  let bridge = &mut ctx.accounts.bridge;
+
+  let (local_token, remote_chain_selector, remote_token) = bridge.take_legacy_route(&token_id)?;
+  require!(local_token == ctx.accounts.token_mint.key(), BridgeErrorCode::DisMatchToken);
+
+  // Vault balance already owed to the mint's other routes and their providers
+  let mut assigned: u64 = 0;
+  let mut routes: Vec<Pubkey> = Vec::with_capacity(ctx.remaining_accounts.len());
+  for account in ctx.remaining_accounts {
+    require!(!routes.contains(account.key), BridgeErrorCode::DisMatchToken);
+    require_keys_eq!(*account.owner, crate::ID, BridgeErrorCode::DisMatchToken);
+    let route = TokenRoute::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+    require!(route.local_token == local_token, BridgeErrorCode::DisMatchToken);
+    assigned = assigned
+      .checked_add(route.target_balance)
+      .ok_or(BridgeErrorCode::Overflow)?;
+    routes.push(account.key());
+  }
+
+  let vault_balance = ctx.accounts.bridge_token_account
+    .as_ref()
+    .map_or(0, |bridge_token_account| bridge_token_account.amount);
+  require!(
+    target_balance <= vault_balance.saturating_sub(assigned),
+    BridgeErrorCode::InsufficientBalance
+  );
+
+  let token_route = &mut ctx.accounts.token_route;
+  token_route.register(
+    token_id,
+    local_token,
+    remote_chain_selector,
+    remote_token,
+    RouteMode::LockRelease,
+    ctx.bumps.token_route
+  )?;
+  token_route.set_decimals(ctx.accounts.token_mint.decimals, remote_decimals)?;
+  token_route.credit(target_balance)?;
+
+  emit!(MigrateTokenRouteEvent {
+    token_id,
+    local_token,
+    token_route: token_route.key(),
+    target_balance
+  });
+
+  Ok(())
+}
 
 #[derive(Accounts)]
 pub struct ManageToken<'info> {
   #[account(mut)]
-  pub owner: Signer<'info>,
+  pub authority: Signer<'info>,
+
+  #[account(
+      seeds = [ROLE_SEED, Role::TokenRegistrar.seed().as_ref(), authority.key().as_ref()],
+      bump = role_member.bump
+  )]
+  pub role_member: Box<Account<'info, RoleMember>>,
 
   #[account(
       mut,
       seeds = [BRIDGE_SEED],
+      bump,
+      constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+  )]
+  pub bridge: Box<Account<'info, Bridge>>,
+}
+
+#[derive(Accounts)]
+#[instruction(local_token: Pubkey, remote_chain_selector: u64, remote_token: [u8; 20])]
+pub struct AddToken<'info> {
+  #[account(mut)]
+  pub authority: Signer<'info>,
+
+  #[account(
+      seeds = [ROLE_SEED, Role::TokenRegistrar.seed().as_ref(), authority.key().as_ref()],
+      bump = role_member.bump
+  )]
+  pub role_member: Box<Account<'info, RoleMember>>,
+
+  #[account(
+      seeds = [BRIDGE_SEED],
+      bump,
+      constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+  )]
+  pub bridge: Box<Account<'info, Bridge>>,
+
+  #[account(address = local_token)]
+  pub token_mint: Box<InterfaceAccount<'info, InterfaceMint>>,
+
+  #[account(
+      init_if_needed,
+      payer = authority,
+      seeds = [
+        TOKEN_ROUTE_SEED,
+        Bridge::get_evm_token_id(bridge.chain_selector, &local_token, remote_chain_selector, &remote_token).as_ref()
+      ],
+      bump,
+      space = 8 + TokenRoute::INIT_SPACE
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+
+  /// CHECK: system owned PDA holding the lamports of native SOL routes, only used by them.
+  #[account(
+      mut,
+      seeds = [SOL_ESCROW_SEED],
       bump
   )]
+  pub sol_escrow: Option<AccountInfo<'info>>,
+
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(remote_chain_selector: u64, remote_token: [u8; 20], remote_decimals: u8)]
+pub struct CreateWrappedToken<'info> {
+  #[account(mut)]
+  pub authority: Signer<'info>,
+
+  #[account(
+      seeds = [ROLE_SEED, Role::TokenRegistrar.seed().as_ref(), authority.key().as_ref()],
+      bump = role_member.bump
+  )]
+  pub role_member: Box<Account<'info, RoleMember>>,
+
+  #[account(
+      seeds = [BRIDGE_SEED],
+      bump,
+      constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+  )]
+  pub bridge: Box<Account<'info, Bridge>>,
+
+  #[account(
+      init,
+      payer = authority,
+      seeds = [WRAPPED_MINT_SEED, remote_chain_selector.to_be_bytes().as_ref(), remote_token.as_ref()],
+      bump,
+      mint::decimals = remote_decimals.min(MAX_WRAPPED_DECIMALS),
+      mint::authority = bridge
+  )]
+  pub token_mint: Box<Account<'info, Mint>>,
+
+  /// CHECK: metadata PDA of token_mint, created and validated by the token metadata program.
+  #[account(
+      mut,
+      seeds = [b"metadata", token_metadata_program.key().as_ref(), token_mint.key().as_ref()],
+      bump,
+      seeds::program = token_metadata_program.key()
+  )]
+  pub metadata: UncheckedAccount<'info>,
+
+  #[account(
+      init_if_needed,
+      payer = authority,
+      seeds = [
+        TOKEN_ROUTE_SEED,
+        Bridge::get_evm_token_id(bridge.chain_selector, &token_mint.key(), remote_chain_selector, &remote_token).as_ref()
+      ],
+      bump,
+      space = 8 + TokenRoute::INIT_SPACE
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+
+  pub token_program: Program<'info, Token>,
+  pub token_metadata_program: Program<'info, Metadata>,
+  pub system_program: Program<'info, System>,
+  pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32], nonce: u64)]
+pub struct RemoveToken<'info> {
+  #[account(
+      mut,
+      close = scheduled_by,
+      seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+      bump = queued_operation.bump
+  )]
+  pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+  /// CHECK: receives the rent of the queued operation and of the route
+  #[account(
+      mut,
+      address = queued_operation.scheduled_by
+  )]
+  pub scheduled_by: AccountInfo<'info>,
+
+  #[account(
+      mut,
+      close = scheduled_by,
+      seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+      bump = token_route.bump
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32], nonce: u64)]
+pub struct SetLpFee<'info> {
+  #[account(
+      mut,
+      close = scheduled_by,
+      seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+      bump = queued_operation.bump
+  )]
+  pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+  /// CHECK: receives the rent of the queued operation
+  #[account(
+      mut,
+      address = queued_operation.scheduled_by
+  )]
+  pub scheduled_by: AccountInfo<'info>,
+
+  #[account(
+      mut,
+      seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+      bump = token_route.bump
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32])]
+pub struct PauseRoute<'info> {
+  pub authority: Signer<'info>,
+
+  #[account(
+      seeds = [ROLE_SEED, Role::Pauser.seed().as_ref(), authority.key().as_ref()],
+      bump = role_member.bump
+  )]
+  pub role_member: Box<Account<'info, RoleMember>>,
+
+  #[account(
+      mut,
+      seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+      bump = token_route.bump
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32])]
+pub struct UnpauseRoute<'info> {
+  pub owner: Signer<'info>,
+
+  #[account(
+      seeds = [BRIDGE_SEED],
+      bump,
+      constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+  )]
   pub bridge: Box<Account<'info, Bridge>>,
+
+  #[account(
+      mut,
+      seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+      bump = token_route.bump
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32], remote_chain_selector: u64, nonce: u64)]
+pub struct SetFeeSchedule<'info> {
+  // pays for the fee schedule account the first time a route gets one
+  #[account(mut)]
+  pub payer: Signer<'info>,
+
+  #[account(
+      mut,
+      close = scheduled_by,
+      seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+      bump = queued_operation.bump
+  )]
+  pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+  /// CHECK: receives the rent of the queued operation
+  #[account(
+      mut,
+      address = queued_operation.scheduled_by
+  )]
+  pub scheduled_by: AccountInfo<'info>,
+
+  #[account(
+      seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+      bump = token_route.bump
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+
+  #[account(
+      init_if_needed,
+      payer = payer,
+      seeds = [FEE_SCHEDULE_SEED, token_id.as_ref(), remote_chain_selector.to_be_bytes().as_ref()],
+      bump,
+      space = 8 + FeeSchedule::INIT_SPACE
+  )]
+  pub fee_schedule: Box<Account<'info, FeeSchedule>>,
+
+  pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32])]
+pub struct MigrateTokenRoute<'info> {
+  #[account(mut)]
+  pub authority: Signer<'info>,
+
+  #[account(
+      seeds = [ROLE_SEED, Role::TokenRegistrar.seed().as_ref(), authority.key().as_ref()],
+      bump = role_member.bump
+  )]
+  pub role_member: Box<Account<'info, RoleMember>>,
+
+  #[account(
+      mut,
+      seeds = [BRIDGE_SEED],
+      bump,
+      constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+  )]
+  pub bridge: Box<Account<'info, Bridge>>,
+
+  pub token_mint: Box<InterfaceAccount<'info, InterfaceMint>>,
+
+  #[account(
+      init,
+      payer = authority,
+      seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+      bump,
+      space = 8 + TokenRoute::INIT_SPACE
+  )]
+  pub token_route: Box<Account<'info, TokenRoute>>,
+
+  // Vault the legacy routes of the mint locked their tokens in, absent if it was never funded
+  #[account(
+      seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
+      bump,
+      token::mint = token_mint,
+      token::authority = bridge,
+      token::token_program = token_program
+  )]
+  pub bridge_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+  pub token_program: Interface<'info, TokenInterface>,
+  pub system_program: Program<'info, System>,
 }
@@ -1,36 +1,252 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, Discriminator};
 
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{ self, Mint, Token, TokenAccount, Transfer }
+    token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked }
   };
-use crate::{state::*, constants::*, error::*, event::*};
-use solana_program::{program::invoke_signed, system_instruction};
+use crate::{state::*, constants::*, error::*, event::*, program::SolBridge};
+use solana_program::{program::{invoke, invoke_signed}, system_instruction, sysvar};
 
 pub fn initialize(ctx: Context<Initialize>, protocol_fee: u64, chain_selecotr: u64) -> Result<()> {
+    require!(chain_selecotr != 0, BridgeErrorCode::InvalidChainSelector);
+
     let accts = ctx.accounts;
     accts.bridge.owner = accts.owner.key();
     accts.bridge.protocol_fee = protocol_fee;
     accts.bridge.chain_selector = chain_selecotr;
     accts.bridge.vault = accts.vault.key();
+    accts.bridge.token_id_version = TOKEN_ID_VERSION_EVM;
+    accts.bridge.timelock_delay = MIN_TIMELOCK_DELAY;
     
     Ok(())
 }
 
-pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, protocol_fee: u64) -> Result<()> {
+// Rewrite a bridge created by the legacy program into the current layout. The stale
+// bytes it left after its route table are zeroed instead of being read as new fields.
+pub fn migrate_bridge_layout(ctx: Context<MigrateBridgeLayout>) -> Result<()> {
+    let accts = ctx.accounts;
+    let bridge_info = accts.bridge.to_account_info();
+    require!(bridge_info.data_len() != BRIDGE_SPACE, BridgeErrorCode::InvalidBridgeLayout);
+
+    let legacy = {
+        let data = bridge_info.try_borrow_data()?;
+        require!(
+            data.len() >= 8 && data[..8] == Bridge::DISCRIMINATOR,
+            BridgeErrorCode::InvalidBridgeLayout
+        );
+        LegacyBridge::deserialize(&mut &data[8..]).map_err(|_| BridgeErrorCode::InvalidBridgeLayout)?
+    };
+    require!(legacy.owner == accts.owner.key(), BridgeErrorCode::InvalidOwner);
+
+    // The owner pays the rent for the larger account
+    let rent = Rent::get()?.minimum_balance(BRIDGE_SPACE).saturating_sub(bridge_info.lamports());
+    if rent > 0 {
+        invoke(
+            &system_instruction::transfer(&accts.owner.key(), &bridge_info.key(), rent),
+            &[
+                accts.owner.to_account_info().clone(),
+                bridge_info.clone(),
+                accts.system_program.to_account_info().clone(),
+            ],
+        )?;
+    }
+    bridge_info.realloc(BRIDGE_SPACE, true)?;
+
+    let mut data = bridge_info.try_borrow_mut_data()?;
+    data.fill(0);
+    legacy.into_bridge().try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, nonce: u64) -> Result<()> {
+    let operation = ctx.accounts.queued_operation.ready()?;
+    let Operation::SetProtocolFee { protocol_fee, protocol_fee_usd_cents } = operation else {
+        return err!(BridgeErrorCode::InvalidOperation);
+    };
     let bridge = &mut ctx.accounts.bridge;
-    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
-    require!(protocol_fee != 0, BridgeErrorCode::InvalidProtocolFee);
     bridge.protocol_fee = protocol_fee;
+    bridge.protocol_fee_usd_cents = protocol_fee_usd_cents;
+
+    emit!(ExecuteOperationEvent {
+        nonce,
+        operation,
+    });
+
+    Ok(())
+}
+
+pub fn propose_owner(ctx: Context<ProposeOwner>, pending_owner: Pubkey) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+    require!(
+        pending_owner != Pubkey::default() && pending_owner != bridge.owner,
+        BridgeErrorCode::InvalidPendingOwner
+    );
+    bridge.pending_owner = pending_owner;
+
+    emit!(ProposeOwnerEvent {
+        owner: bridge.owner,
+        pending_owner,
+    });
+
+    Ok(())
+}
+
+pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    let pending_owner = ctx.accounts.pending_owner.key();
+    require!(
+        bridge.pending_owner != Pubkey::default() && bridge.pending_owner == pending_owner,
+        BridgeErrorCode::InvalidPendingOwner
+    );
+
+    let previous_owner = bridge.owner;
+    bridge.owner = pending_owner;
+    bridge.pending_owner = Pubkey::default();
+
+    emit!(AcceptOwnerEvent {
+        previous_owner,
+        owner: pending_owner,
+    });
+
+    Ok(())
+}
+
+// The owner cancels its proposal, or the pending owner renounces it
+pub fn cancel_owner_proposal(ctx: Context<CancelOwnerProposal>) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    let signer = ctx.accounts.signer.key();
+    require!(bridge.pending_owner != Pubkey::default(), BridgeErrorCode::InvalidPendingOwner);
+    require!(
+        signer == bridge.owner || signer == bridge.pending_owner,
+        BridgeErrorCode::InvalidOwner
+    );
+
+    let pending_owner = bridge.pending_owner;
+    bridge.pending_owner = Pubkey::default();
+
+    emit!(CancelOwnerProposalEvent {
+        pending_owner,
+        cancelled_by: signer,
+    });
+
+    Ok(())
+}
+
+pub fn grant_role(ctx: Context<GrantRole>, role: Role, member: Pubkey) -> Result<()> {
+    let bridge = &ctx.accounts.bridge;
+    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+
+    let role_member = &mut ctx.accounts.role_member;
+    role_member.role = role;
+    role_member.member = member;
+    role_member.bump = ctx.bumps.role_member;
+
+    emit!(GrantRoleEvent {
+        role,
+        member,
+    });
+
     Ok(())
 }
 
-pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+pub fn revoke_role(ctx: Context<RevokeRole>, role: Role, member: Pubkey) -> Result<()> {
+    let bridge = &ctx.accounts.bridge;
+    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+
+    emit!(RevokeRoleEvent {
+        role,
+        member,
+    });
+
+    Ok(())
+}
+
+pub fn pause(ctx: Context<Pause>) -> Result<()> {
+    ctx.accounts.bridge.paused = true;
+
+    emit!(SetPausedEvent {
+        paused: true,
+    });
+
+    Ok(())
+}
+
+// Only the owner can resume the bridge after an incident
+pub fn unpause(ctx: Context<Unpause>) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+    bridge.paused = false;
+
+    emit!(SetPausedEvent {
+        paused: false,
+    });
+
+    Ok(())
+}
+
+pub fn set_max_referrer_bps(ctx: Context<SetMaxReferrerBps>, max_referrer_bps: u16) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    require!(max_referrer_bps as u64 <= BPS_DENOMINATOR, BridgeErrorCode::InvalidReferrerFee);
+    bridge.max_referrer_bps = max_referrer_bps;
+    Ok(())
+}
+
+pub fn set_price_feed(ctx: Context<SetPriceFeed>, nonce: u64) -> Result<()> {
+    let operation = ctx.accounts.queued_operation.ready()?;
+    let Operation::SetPriceFeed { price_feed, max_price_age, max_price_conf_bps } = operation else {
+        return err!(BridgeErrorCode::InvalidOperation);
+    };
+    let bridge = &mut ctx.accounts.bridge;
+    bridge.set_price_feed(price_feed, max_price_age, max_price_conf_bps)?;
+
+    emit!(SetPriceFeedEvent {
+        price_feed,
+        max_price_age,
+        max_price_conf_bps,
+    });
+
+    emit!(ExecuteOperationEvent {
+        nonce,
+        operation,
+    });
+
+    Ok(())
+}
+
+pub fn set_guardians(ctx: Context<SetGuardians>, guardians: Vec<Pubkey>, threshold: u8) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+
+    // The owner alone only sets the first guardian set. Replacing one takes the
+    // current guardians' signatures, checked like those of a message_receive
+    if !bridge.guardians.is_empty() {
+        let digest = bridge.guardian_set_digest(&guardians, threshold);
+        bridge.verify_guardian_signatures(&ctx.accounts.instructions, &digest)?;
+    }
+    bridge.set_guardians(guardians.clone(), threshold)?;
+
+    emit!(SetGuardiansEvent {
+        guardians,
+        threshold,
+        guardian_set_index: bridge.guardian_set_index,
+    });
+
+    Ok(())
+}
+
+pub fn withdraw(ctx: Context<Withdraw>, nonce: u64) -> Result<()> {
     let accts = ctx.accounts;
 
-    require!(accts.bridge.owner == accts.owner.key(), BridgeErrorCode::InvalidOwner);
+    let operation = accts.queued_operation.ready()?;
+    let Operation::Withdraw { beneficiary, amount } = operation else {
+        return err!(BridgeErrorCode::InvalidOperation);
+    };
+    require!(beneficiary == accts.beneficiary.key(), BridgeErrorCode::InvalidOperation);
 
-    let lamports = accts.vault.to_account_info().lamports();
+    // The vault keeps its rent-exempt minimum
+    let lamports = accts.vault.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
     require!(amount <= lamports, BridgeErrorCode::InsufficientBalance);
 
     let (_, bump) = Pubkey::find_program_address(&[VAULT_SEED], &crate::ID);
@@ -49,21 +265,90 @@ pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
         beneficiary: accts.beneficiary.key(),
     });
 
+    emit!(ExecuteOperationEvent {
+        nonce,
+        operation,
+    });
+
     Ok(())
 }
 
-pub fn withdraw_token(ctx: Context<WithdrawToken>, token_id: String, amount: u64) -> Result<()> {
-    let bridge = &ctx.accounts.bridge;
+pub fn set_fee_recipients(ctx: Context<SetFeeRecipients>, fee_recipients: Vec<FeeRecipient>) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    bridge.set_fee_recipients(fee_recipients.clone())?;
 
-    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+    emit!(SetFeeRecipientsEvent {
+        fee_recipients,
+    });
+
+    Ok(())
+}
+
+// Pay the protocol fees held by the vault to the fee recipients, passed as
+// remaining accounts in the order of bridge.fee_recipients
+pub fn distribute_fees<'info>(ctx: Context<'_, '_, '_, 'info, DistributeFees<'info>>) -> Result<()> {
+    let accts = &ctx.accounts;
+    let fee_recipients = &accts.bridge.fee_recipients;
+
+    require!(!fee_recipients.is_empty(), BridgeErrorCode::InvalidFeeRecipients);
+    require!(ctx.remaining_accounts.len() == fee_recipients.len(), BridgeErrorCode::InvalidFeeRecipients);
+
+    // The vault keeps its rent-exempt minimum
+    let amount = accts.vault.lamports().saturating_sub(Rent::get()?.minimum_balance(0));
+    require!(amount > 0, BridgeErrorCode::InsufficientBalance);
+
+    let amounts = accts.bridge.fee_shares(amount);
+    let mut recipients = Vec::with_capacity(fee_recipients.len());
+
+    for ((fee_recipient, beneficiary), share) in fee_recipients.iter().zip(ctx.remaining_accounts).zip(&amounts) {
+        require!(beneficiary.key() == fee_recipient.recipient, BridgeErrorCode::InvalidFeeRecipients);
+        recipients.push(beneficiary.key());
+        if *share == 0 {
+            continue;
+        }
+
+        invoke_signed(
+            &system_instruction::transfer(&accts.vault.key(), &beneficiary.key(), *share),
+            &[
+                accts.vault.to_account_info().clone(),
+                beneficiary.clone(),
+                accts.system_program.to_account_info().clone(),
+            ],
+            &[&[VAULT_SEED, &[ctx.bumps.vault]]],
+        )?;
+    }
+
+    emit!(DistributeFeesEvent {
+        recipients,
+        amounts,
+    });
+
+    Ok(())
+}
 
-    // Check if token is supported
-    require!(bridge.token_ids.contains(&token_id), BridgeErrorCode::UnsupportedToken);
+pub fn withdraw_token(ctx: Context<WithdrawToken>, _token_id: [u8; 32], nonce: u64) -> Result<()> {
+    let operation = ctx.accounts.queued_operation.ready()?;
+    let Operation::WithdrawToken { token_id, beneficiary, amount } = operation else {
+        return err!(BridgeErrorCode::InvalidOperation);
+    };
+    require!(
+        token_id == ctx.accounts.token_route.token_id && beneficiary == ctx.accounts.beneficiary.key(),
+        BridgeErrorCode::InvalidOperation
+    );
 
     // Get the token address
-    let token_mint = bridge.get_token_address(token_id).ok_or(BridgeErrorCode::UnsupportedToken)?;
+    let token_mint = ctx.accounts.token_route.local_token;
+
+    require!(token_mint == ctx.accounts.token_mint.key(), BridgeErrorCode::DisMatchToken);
 
-    require!(token_mint == &ctx.accounts.token_mint.key(), BridgeErrorCode::DisMatchToken);
+    // Only withdraw what was locked for this route, provider liquidity is
+    // returned through remove_liquidity
+    let token_route = &mut ctx.accounts.token_route;
+    require!(
+        amount <= token_route.target_balance.saturating_sub(token_route.liquidity),
+        BridgeErrorCode::InsufficientBalance
+    );
+    token_route.debit(amount)?;
 
     let token_program = &ctx.accounts.token_program;
     let bridge_token_account = &ctx.accounts.bridge_token_account;
@@ -77,17 +362,49 @@ pub fn withdraw_token(ctx: Context<WithdrawToken>, token_id: String, amount: u64
     let signer = &[&vault_seeds[..]];
 
     // Transfer tokens from bridge to beneficiary
-    let cpi_accounts = Transfer {
+    let cpi_accounts = TransferChecked {
         from: bridge_token_account.to_account_info(),
+        mint: ctx.accounts.token_mint.to_account_info(),
         to: beneficiary_token_account.to_account_info(),
         authority: ctx.accounts.bridge.to_account_info(),
     };
     let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-    token::transfer(cpi_context.with_signer(signer), amount)?;
+    token_interface::transfer_checked(cpi_context.with_signer(signer), amount, ctx.accounts.token_mint.decimals)?;
 
     emit!(
         WithdrawTokenEvent {
-            token: *token_mint,
+            token: token_mint,
+            amount,
+        }
+    );
+
+    emit!(ExecuteOperationEvent {
+        nonce,
+        operation,
+    });
+
+    Ok(())
+}
+
+pub fn withdraw_token_fees(ctx: Context<WithdrawTokenFees>, amount: u64) -> Result<()> {
+    let fee_token_account = &ctx.accounts.fee_token_account;
+    require!(amount <= fee_token_account.amount, BridgeErrorCode::InsufficientBalance);
+
+    let signer: &[&[&[u8]]] = &[&[BRIDGE_SEED, &[ctx.bumps.bridge]]];
+
+    // Transfer collected token fees from the fee account to beneficiary
+    let cpi_accounts = TransferChecked {
+        from: fee_token_account.to_account_info(),
+        mint: ctx.accounts.token_mint.to_account_info(),
+        to: ctx.accounts.beneficiary_token_account.to_account_info(),
+        authority: ctx.accounts.bridge.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    token_interface::transfer_checked(cpi_context.with_signer(signer), amount, ctx.accounts.token_mint.decimals)?;
+
+    emit!(
+        WithdrawTokenFeesEvent {
+            token: ctx.accounts.token_mint.key(),
             amount,
         }
     );
@@ -102,7 +419,7 @@ pub struct Initialize<'info> {
         payer = owner, 
         seeds = [BRIDGE_SEED],
         bump,
-        space = 5000
+        space = BRIDGE_SPACE
     )]
     pub bridge: Box<Account<'info, Bridge>>,
     #[account(mut)]
@@ -114,35 +431,270 @@ pub struct Initialize<'info> {
         bump
     )]
     pub vault: AccountInfo<'info>,
+    // Only the upgrade authority of the deployed program can initialize it,
+    // so nobody can front-run the deployment and take ownership of the bridge
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, SolBridge>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(owner.key()) @ BridgeErrorCode::InvalidOwner)]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateBridgeLayout<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    /// CHECK: read with the legacy layout and rewritten by migrate_bridge_layout.
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        owner = crate::ID
+    )]
+    pub bridge: AccountInfo<'info>,
+
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
+#[instruction(nonce: u64)]
 pub struct SetProtocolFee<'info> {
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        mut,
+        close = scheduled_by,
+        seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+        bump = queued_operation.bump
+    )]
+    pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+    /// CHECK: receives the rent of the queued operation
+    #[account(
+        mut,
+        address = queued_operation.scheduled_by
+    )]
+    pub scheduled_by: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxReferrerBps<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ROLE_SEED, Role::FeeManager.seed().as_ref(), authority.key().as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeOwner<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
 
     #[account(
         mut,
         seeds = [BRIDGE_SEED],
-        bump
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
     )]
     pub bridge: Box<Account<'info, Bridge>>,
 }
 
+#[derive(Accounts)]
+pub struct AcceptOwner<'info> {
+    #[account(mut)]
+    pub pending_owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+}
 
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
+pub struct CancelOwnerProposal<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+}
+
+#[derive(Accounts)]
+#[instruction(role: Role, member: Pubkey)]
+pub struct GrantRole<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
 
+    #[account(
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        init,
+        payer = owner,
+        seeds = [ROLE_SEED, role.seed().as_ref(), member.as_ref()],
+        bump,
+        space = 8 + RoleMember::INIT_SPACE
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(role: Role, member: Pubkey)]
+pub struct RevokeRole<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [ROLE_SEED, role.seed().as_ref(), member.as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+}
+
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ROLE_SEED, Role::Pauser.seed().as_ref(), authority.key().as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
     #[account(
         mut,
         seeds = [BRIDGE_SEED],
-        bump
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
     )]
     pub bridge: Box<Account<'info, Bridge>>,
-    
+}
+
+#[derive(Accounts)]
+pub struct Unpause<'info> {
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct SetPriceFeed<'info> {
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        mut,
+        close = scheduled_by,
+        seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+        bump = queued_operation.bump
+    )]
+    pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+    /// CHECK: receives the rent of the queued operation
+    #[account(
+        mut,
+        address = queued_operation.scheduled_by
+    )]
+    pub scheduled_by: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardians<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    /// CHECK: instructions sysvar, used to read the current guardians' Ed25519 signatures.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct Withdraw<'info> {
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        mut,
+        close = scheduled_by,
+        seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+        bump = queued_operation.bump
+    )]
+    pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+    /// CHECK: receives the rent of the queued operation
+    #[account(
+        mut,
+        address = queued_operation.scheduled_by
+    )]
+    pub scheduled_by: AccountInfo<'info>,
+
     /// CHECK:` doc comment explaining why no checks through types are necessary.
     #[account(
         mut,
@@ -159,34 +711,150 @@ pub struct Withdraw<'info> {
 
 
 #[derive(Accounts)]
-pub struct WithdrawToken<'info> {
+pub struct SetFeeRecipients<'info> {
     #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ROLE_SEED, Role::FeeManager.seed().as_ref(), authority.key().as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+}
+
+#[derive(Accounts)]
+pub struct DistributeFees<'info> {
+    #[account(
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    /// CHECK:` doc comment explaining why no checks through types are necessary.
+    #[account(
+        mut,
+        seeds = [VAULT_SEED],
+        bump
+    )]
+    pub vault: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32], nonce: u64)]
+pub struct WithdrawToken<'info> {
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
     pub bridge: Account<'info, Bridge>,
 
+    // Pays for the beneficiary token account if it does not exist yet
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        close = scheduled_by,
+        seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+        bump = queued_operation.bump
+    )]
+    pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+    /// CHECK: receives the rent of the queued operation
+    #[account(
+        mut,
+        address = queued_operation.scheduled_by
+    )]
+    pub scheduled_by: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
 
     #[account(mut)]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(
         mut,
         seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
         bump,
         token::mint = token_mint,
-        token::authority = bridge
+        token::authority = bridge,
+        token::token_program = token_program
     )]
-    pub bridge_token_account: Box<Account<'info, TokenAccount>>,
+    pub bridge_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
  
+    /// CHECK: checked against the queued operation
+    pub beneficiary: AccountInfo<'info>,
+
     #[account(
         init_if_needed,
-        payer = owner,
+        payer = payer,
         associated_token::mint = token_mint,
-        associated_token::authority = owner
+        associated_token::authority = beneficiary,
+        associated_token::token_program = token_program
     )]
-    pub beneficiary_token_account: Box<Account<'info, TokenAccount>>,
+    pub beneficiary_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>
-}
\ No newline at end of file
+}
+#[derive(Accounts)]
+pub struct WithdrawTokenFees<'info> {
+    #[account(
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ROLE_SEED, Role::Treasurer.seed().as_ref(), authority.key().as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        seeds = [FEE_COLLECTOR_SEED, token_mint.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = bridge,
+        token::token_program = token_program
+    )]
+    pub fee_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        associated_token::mint = token_mint,
+        associated_token::authority = authority,
+        associated_token::token_program = token_program
+    )]
+    pub beneficiary_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>
+}
@@ -1,7 +1,9 @@
 pub mod initialize;
 pub mod bridge_token;
 pub mod sol_bridge;
+pub mod timelock;
 
 pub use initialize::*;
 pub use bridge_token::*;
 pub use sol_bridge::*;
+pub use timelock::*;
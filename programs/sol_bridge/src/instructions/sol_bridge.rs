@@ -2,56 +2,259 @@ use anchor_lang::prelude::*;
 
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{ self, Mint, Token, TokenAccount, Transfer }
+    token_interface::{ self, Burn, Mint, MintTo, TokenAccount, TokenInterface, TransferChecked }
   };
 use crate::{state::*, constants::*, error::*, event::*};
-use solana_program::{program::invoke, system_instruction};
+use solana_program::{program::{invoke, invoke_signed}, system_instruction, sysvar};
 
-pub fn add_liquidity(ctx: Context<AddLiquidity>, amount: u64, remote_chain_selector: u64, remote_token: String) -> Result<()> {
+// Transfer `amount` from the user into the bridge vault and return what the vault
+// actually received, which is less than `amount` for mints with a transfer fee
+fn lock_tokens<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    token_mint: &InterfaceAccount<'info, Mint>,
+    token_account: &InterfaceAccount<'info, TokenAccount>,
+    user: &Signer<'info>,
+    bridge_token_account: &mut InterfaceAccount<'info, TokenAccount>,
+    amount: u64
+) -> Result<u64> {
+    let balance_before = bridge_token_account.amount;
+
+    // Transfer tokens from user to bridge
+    let cpi_accounts = TransferChecked {
+        from: token_account.to_account_info(),
+        mint: token_mint.to_account_info(),
+        to: bridge_token_account.to_account_info(),
+        authority: user.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+    token_interface::transfer_checked(cpi_context, amount, token_mint.decimals)?;
+
+    bridge_token_account.reload()?;
+    bridge_token_account.amount
+        .checked_sub(balance_before)
+        .ok_or(BridgeErrorCode::Underflow.into())
+}
+
+// What a send of `amount` charges and delivers, checked against the sender's limits.
+// quote_send goes through here too, so quotes cannot drift from the charge.
+#[allow(clippy::too_many_arguments)]
+fn quote<'info>(
+    bridge: &Bridge,
+    token_route: &TokenRoute,
+    token_mint: &AccountInfo<'info>,
+    fee_schedule: &AccountInfo<'info>,
+    price_feed: Option<&AccountInfo<'info>>,
+    amount: u64,
+    max_fee_lamports: u64,
+    min_amount_out: u128,
+    referrer_bps: Option<u16>
+) -> Result<SendQuote> {
+    require!(amount > 0, BridgeErrorCode::AmountTooSmall);
+
+    // The route's fee schedule, or the global protocol fee when it has none
+    let mut fees = match FeeSchedule::load(fee_schedule)? {
+        Some(fee_schedule) => fee_schedule.send_fees(amount),
+        None => SendFees {
+            lamport_fee: bridge.protocol_fee_lamports(price_feed)?,
+            token_fee: 0,
+        },
+    };
+    require!(amount > fees.token_fee, BridgeErrorCode::AmountTooSmall);
+
+    // Native routes pay the token fee in lamports together with the flat fee
+    if token_route.mode == RouteMode::Native {
+        fees.lamport_fee = fees.lamport_fee.checked_add(fees.token_fee).ok_or(BridgeErrorCode::Overflow)?;
+    }
+    let amount = amount - fees.token_fee;
+
+    // Lock/release routes lose the mint's transfer fee on the way into the vault,
+    // and keep the liquidity fee out of what reaches it for the route's providers
+    let (transfer_fee, lp_fee) = match token_route.mode {
+        RouteMode::LockRelease => {
+            let transfer_fee = TokenRoute::transfer_fee(token_mint, amount)?;
+            let received = amount.checked_sub(transfer_fee).ok_or(BridgeErrorCode::AmountTooSmall)?;
+            (transfer_fee, token_route.lp_fee(received, token_route.send_lp_fee_bps))
+        }
+        _ => (0, 0),
+    };
+    let received = amount - transfer_fee - lp_fee;
+
+    // Dust is only taken off once every fee is, so only what the remote token
+    // can represent is bridged and the rest stays with the sender
+    let amount = token_route.truncate_dust(received);
+    let dust = received - amount;
+    let remote_amount = token_route.to_remote_amount(amount)?;
+    require!(remote_amount > 0, BridgeErrorCode::AmountTooSmall);
+
+    // Only sends through a referral account share the lamport fee
+    let referral_fee = match referrer_bps {
+        Some(referrer_bps) => Referral::referral_fee(fees.lamport_fee, referrer_bps, bridge.max_referrer_bps)?,
+        None => 0,
+    };
+
+    // Fees may have been raised since the sender signed
+    require!(
+        fees.lamport_fee <= max_fee_lamports && remote_amount >= min_amount_out,
+        BridgeErrorCode::FeeLimitExceeded
+    );
+
+    Ok(SendQuote {
+        lamport_fee: fees.lamport_fee,
+        referral_fee,
+        token_fee: fees.token_fee,
+        transfer_fee,
+        lp_fee,
+        dust,
+        amount,
+        remote_amount,
+        available_liquidity: token_route.target_balance,
+    })
+}
+
+pub fn add_liquidity(ctx: Context<AddLiquidity>, amount: u64, remote_chain_selector: u64, remote_token: [u8; 20]) -> Result<()> {
     let accts = ctx.accounts;
 
-    let bridge = &accts.bridge;
     let user = &accts.user;
     let local_token = accts.token_mint.key();
 
-    require!(bridge.owner == user.key(), BridgeErrorCode::InvalidOwner);
+    // The route PDA is derived from the mint, so it only exists for supported tokens
+    require!(accts.token_route.local_token == local_token, BridgeErrorCode::DisMatchToken);
+    require!(accts.token_route.mode == RouteMode::LockRelease, BridgeErrorCode::InvalidRouteMode);
+
+    let amount = lock_tokens(
+        &accts.token_program,
+        &accts.token_mint,
+        &accts.token_account,
+        user,
+        &mut accts.bridge_token_account,
+        amount
+    )?;
 
-    // Encode local_token as bytes
-    let binding = local_token.to_string();
-    let local_token_bytes = binding.as_bytes();
+    accts.token_route.sync_lp_shares(accts.lp_mint.supply);
+    let shares = accts.token_route.deposit_liquidity(amount)?;
 
-    let token_id = bridge.get_token_id(
-        local_token_bytes,
-        bridge.chain_selector,
-        remote_chain_selector,
-        remote_token.as_bytes()
+    // Mint LP shares to the provider
+    let signer: &[&[&[u8]]] = &[&[BRIDGE_SEED, &[ctx.bumps.bridge]]];
+    let cpi_accounts = MintTo {
+        mint: accts.lp_mint.to_account_info(),
+        to: accts.lp_token_account.to_account_info(),
+        authority: accts.bridge.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+    token_interface::mint_to(cpi_context.with_signer(signer), shares)?;
+
+    // A new position only counts fees from now on, shares the provider got
+    // by transfer before opening it included
+    let lp_position = &mut accts.lp_position;
+    let shares_before = if lp_position.provider == Pubkey::default() {
+        0
+    } else {
+        accts.lp_token_account.amount
+    };
+    lp_position.provider = user.key();
+    lp_position.token_id = accts.token_route.token_id;
+    lp_position.deposit(
+        shares_before,
+        accts.lp_token_account.amount.checked_add(shares).ok_or(BridgeErrorCode::Overflow)?,
+        accts.token_route.acc_fee_per_share
     )?;
 
-    // Check if token is supported
-    require!(bridge.token_ids.contains(&token_id), BridgeErrorCode::UnsupportedToken);
+    // Emit event
+    emit!(AddLiquidityEvent {
+        provider: user.key(),
+        local_token,
+        amount,
+        shares,
+        remote_chain_selector,
+        remote_token,
+    });
 
-    // Get the token address
-    let token_address = bridge.get_token_address(token_id).ok_or(BridgeErrorCode::UnsupportedToken)?;
+    Ok(())
+}
 
-    require!(token_address == &local_token, BridgeErrorCode::DisMatchToken);
+pub fn remove_liquidity(ctx: Context<RemoveLiquidity>, shares: u64, remote_chain_selector: u64, remote_token: [u8; 20]) -> Result<()> {
+    let accts = ctx.accounts;
+    let local_token = accts.token_mint.key();
 
-    let token_program = &accts.token_program;
-    let token_account = &accts.token_account;
-    let bridge_token_account = &accts.bridge_token_account;
+    require!(accts.token_route.local_token == local_token, BridgeErrorCode::DisMatchToken);
 
-    // Transfer tokens from user to bridge
-    let cpi_accounts = Transfer {
-        from: token_account.to_account_info(),
-        to: bridge_token_account.to_account_info(),
-        authority: user.to_account_info(),
+    accts.token_route.sync_lp_shares(accts.lp_mint.supply);
+    let amount = accts.token_route.withdraw_liquidity(shares)?;
+
+    // Burn the provider's LP shares, fees they earned are part of their value
+    let cpi_accounts = Burn {
+        mint: accts.lp_mint.to_account_info(),
+        from: accts.lp_token_account.to_account_info(),
+        authority: accts.user.to_account_info(),
     };
-    let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-    token::transfer(cpi_context, amount)?;
+    let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+    token_interface::burn(cpi_context, shares)?;
 
-    // Emit event
-    emit!(AddLiquidityEvent {
+    // Transfer the proportional liquidity from bridge to provider
+    let signer: &[&[&[u8]]] = &[&[BRIDGE_SEED, &[ctx.bumps.bridge]]];
+    let cpi_accounts = TransferChecked {
+        from: accts.bridge_token_account.to_account_info(),
+        mint: accts.token_mint.to_account_info(),
+        to: accts.token_account.to_account_info(),
+        authority: accts.bridge.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+    token_interface::transfer_checked(cpi_context.with_signer(signer), amount, accts.token_mint.decimals)?;
+
+    emit!(RemoveLiquidityEvent {
+        provider: accts.user.key(),
+        local_token,
+        amount,
+        shares,
+        remote_chain_selector,
+        remote_token,
+    });
+
+    Ok(())
+}
+
+pub fn claim_lp_fees(ctx: Context<ClaimLpFees>, remote_chain_selector: u64, remote_token: [u8; 20]) -> Result<()> {
+    let accts = ctx.accounts;
+    let local_token = accts.token_mint.key();
+
+    require!(accts.token_route.local_token == local_token, BridgeErrorCode::DisMatchToken);
+
+    // Fees are already part of the shares' value, claiming them redeems just
+    // enough of the provider's shares, at their current value
+    accts.token_route.sync_lp_shares(accts.lp_mint.supply);
+    let balance = accts.lp_token_account.amount;
+    let acc_fee_per_share = accts.token_route.acc_fee_per_share;
+    let earned = accts.lp_position.earned(balance, acc_fee_per_share)?;
+    require!(earned > 0, BridgeErrorCode::NoLpFees);
+
+    let shares = accts.token_route.shares_for(earned)?.min(balance);
+    let amount = accts.token_route.withdraw_liquidity(shares)?;
+    accts.lp_position.claim(acc_fee_per_share);
+
+    let cpi_accounts = Burn {
+        mint: accts.lp_mint.to_account_info(),
+        from: accts.lp_token_account.to_account_info(),
+        authority: accts.user.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+    token_interface::burn(cpi_context, shares)?;
+
+    // Transfer the earned fees from bridge to provider
+    let signer: &[&[&[u8]]] = &[&[BRIDGE_SEED, &[ctx.bumps.bridge]]];
+    let cpi_accounts = TransferChecked {
+        from: accts.bridge_token_account.to_account_info(),
+        mint: accts.token_mint.to_account_info(),
+        to: accts.token_account.to_account_info(),
+        authority: accts.bridge.to_account_info(),
+    };
+    let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+    token_interface::transfer_checked(cpi_context.with_signer(signer), amount, accts.token_mint.decimals)?;
+
+    emit!(ClaimLpFeesEvent {
+        provider: accts.user.key(),
         local_token,
         amount,
+        shares,
         remote_chain_selector,
         remote_token,
     });
@@ -59,65 +262,204 @@ pub fn add_liquidity(ctx: Context<AddLiquidity>, amount: u64, remote_chain_selec
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn send(
     ctx: Context<Send>, 
     amount: u64, 
     remote_bridge: String,
     remote_chain_selector: u64, 
-    remote_token: String
+    remote_token: [u8; 20],
+    recipient: [u8; 20],
+    max_fee_lamports: u64,
+    min_amount_out: u128,
+    referrer_bps: u16
 ) -> Result<()> {
     let accts = ctx.accounts;
     let local_token = accts.token_mint.key();
 
-    // Encode local_token as bytes
-    let binding = local_token.to_string();
-    let local_token_bytes = binding.as_bytes();
+    // EVM address that gets paid on the remote chain
+    require!(recipient != [0u8; 20], BridgeErrorCode::InvalidRecipient);
 
-    let token_id = accts.bridge.get_token_id(
-        local_token_bytes,
-        accts.bridge.chain_selector,
-        remote_chain_selector,
-        remote_token.as_bytes()
+    let token_route = &accts.token_route;
+    require!(token_route.local_token == local_token, BridgeErrorCode::DisMatchToken);
+    token_route.check_send_open(&accts.bridge)?;
+    let token_id = token_route.token_id;
+
+    let quote = quote(
+        &accts.bridge,
+        token_route,
+        &accts.token_mint.to_account_info(),
+        &accts.fee_schedule,
+        accts.price_feed.as_ref(),
+        amount,
+        max_fee_lamports,
+        min_amount_out,
+        accts.referral.as_ref().map(|_| referrer_bps)
     )?;
+    let token_fee = quote.token_fee;
+    let sol_amount = quote.lamport_fee;
+    let amount = quote.amount;
+    let lp_fee = quote.lp_fee;
 
-    // Check if token is supported
-    require!(accts.bridge.token_ids.contains(&token_id.clone()), BridgeErrorCode::UnsupportedToken);
+    // Token fees go to the fee collection account, native routes pay them in lamports
+    if token_fee > 0 && token_route.mode != RouteMode::Native {
+        let token_account = accts.token_account
+            .as_ref()
+            .ok_or(BridgeErrorCode::MissingRouteAccount)?;
 
-    // Get the token address
-    let token_address = accts.bridge.get_token_address(token_id.clone()).ok_or(BridgeErrorCode::UnsupportedToken)?;
+        let fee_token_account = accts.fee_token_account
+            .as_ref()
+            .ok_or(BridgeErrorCode::MissingFeeTokenAccount)?;
 
+        let cpi_accounts = TransferChecked {
+            from: token_account.to_account_info(),
+            mint: accts.token_mint.to_account_info(),
+            to: fee_token_account.to_account_info(),
+            authority: accts.user.to_account_info(),
+        };
+        let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+        token_interface::transfer_checked(cpi_context, token_fee, accts.token_mint.decimals)?;
+    }
 
-    require!(token_address == &local_token, BridgeErrorCode::DisMatchToken);
+    match token_route.mode {
+        RouteMode::LockRelease => {
+            let bridge_token_account = accts.bridge_token_account
+                .as_mut()
+                .ok_or(BridgeErrorCode::MissingBridgeTokenAccount)?;
 
-    let token_program = &accts.token_program;
-    let token_account = &accts.token_account;
-    let bridge_token_account = &accts.bridge_token_account;
+            let token_account = accts.token_account
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingRouteAccount)?;
 
-    // Transfer tokens from user to bridge
-    let cpi_accounts = Transfer {
-        from: token_account.to_account_info(),
-        to: bridge_token_account.to_account_info(),
-        authority: accts.user.to_account_info(),
-    };
-    let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-    token::transfer(cpi_context, amount)?;
+            // Only the delivered amount and the liquidity fee are locked. A transfer fee is
+            // charged on the whole amount the quote was made for, so on those mints the dust
+            // is locked with it and refunded
+            let refund = if quote.transfer_fee > 0 { quote.dust } else { 0 };
+            let received = lock_tokens(
+                &accts.token_program,
+                &accts.token_mint,
+                token_account,
+                &accts.user,
+                bridge_token_account,
+                amount + lp_fee + refund + quote.transfer_fee
+            )?;
+            require_eq!(received, amount + lp_fee + refund, BridgeErrorCode::InsufficientBalance);
+
+            if refund > 0 {
+                let signer: &[&[&[u8]]] = &[&[BRIDGE_SEED, &[ctx.bumps.bridge]]];
+                let cpi_accounts = TransferChecked {
+                    from: bridge_token_account.to_account_info(),
+                    mint: accts.token_mint.to_account_info(),
+                    to: token_account.to_account_info(),
+                    authority: accts.bridge.to_account_info(),
+                };
+                let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+                token_interface::transfer_checked(cpi_context.with_signer(signer), refund, accts.token_mint.decimals)?;
+            }
+
+            // The liquidity fee stays in the vault for the route's providers
+            let token_route = &mut accts.token_route;
+            token_route.accrue_lp_fee(lp_fee)?;
+
+            // Tokens locked here are owed back when the remote side sends them home
+            token_route.credit(amount)?;
+        }
+        RouteMode::BurnMint => {
+            let token_account = accts.token_account
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingRouteAccount)?;
+
+            // Burn the wrapped tokens, the remote side releases the originals
+            let cpi_accounts = Burn {
+                mint: accts.token_mint.to_account_info(),
+                from: token_account.to_account_info(),
+                authority: accts.user.to_account_info(),
+            };
+            let cpi_context = CpiContext::new(accts.token_program.to_account_info(), cpi_accounts);
+            token_interface::burn(cpi_context, amount)?;
+        }
+        RouteMode::Native => {
+            let sol_escrow = accts.sol_escrow
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingRouteAccount)?;
 
-    // 2-Format display values rounded to nearest dollar
-    let sol_amount = accts.bridge.protocol_fee;
+            // Lock lamports in the escrow, kept apart from the protocol fee vault
+            invoke(
+                &system_instruction::transfer(
+                    &accts.user.key(),
+                    &sol_escrow.key(),
+                    amount
+                ),
+                &[
+                    accts.user.to_account_info().clone(),
+                    sol_escrow.clone(),
+                    accts.system_program.to_account_info().clone(),
+                ],
+            )?;
+
+            accts.token_route.credit(amount)?;
+        }
+    }
+    let remote_amount = quote.remote_amount;
+
+    // The referrer's part of the fee is held by its referral account until claimed
+    let referral_fee = quote.referral_fee;
+    let referrer = match accts.referral.as_mut() {
+        Some(referral) => {
+            referral.credit(referral_fee)?;
+
+            if referral_fee > 0 {
+                invoke(
+                    &system_instruction::transfer(
+                        &accts.user.key(),
+                        &referral.key(),
+                        referral_fee
+                    ),
+                    &[
+                        accts.user.to_account_info().clone(),
+                        referral.to_account_info().clone(),
+                        accts.system_program.to_account_info().clone(),
+                    ],
+                )?;
+            }
+            Some(referral.referrer)
+        }
+        None => None,
+    };
 
     // transfer protocol fee to vault address
-    invoke(
-        &system_instruction::transfer(
-            &accts.user.key(),
-            &accts.vault.key(),
-            sol_amount
-        ),
-        &[
-            accts.user.to_account_info().clone(),
-            accts.vault.clone(),
-            accts.system_program.to_account_info().clone(),
-        ],
-    )?;
+    let vault_fee = sol_amount - referral_fee;
+    if vault_fee > 0 {
+        invoke(
+            &system_instruction::transfer(
+                &accts.user.key(),
+                &accts.vault.key(),
+                vault_fee
+            ),
+            &[
+                accts.user.to_account_info().clone(),
+                accts.vault.clone(),
+                accts.system_program.to_account_info().clone(),
+            ],
+        )?;
+    }
+
+    // Record the message under the next nonce for the destination chain
+    let outbound_sequence = &mut accts.outbound_sequence;
+    let nonce = outbound_sequence.sequence;
+    outbound_sequence.remote_chain_selector = remote_chain_selector;
+    outbound_sequence.sequence = nonce.checked_add(1).ok_or(BridgeErrorCode::Overflow)?;
+
+    let outbound_message = &mut accts.outbound_message;
+    outbound_message.nonce = nonce;
+    outbound_message.remote_chain_selector = remote_chain_selector;
+    outbound_message.sender = accts.user.key();
+    outbound_message.recipient = recipient;
+    outbound_message.token_id = token_id;
+    outbound_message.amount = amount;
+    outbound_message.remote_amount = remote_amount;
+    outbound_message.fee = sol_amount;
+    outbound_message.slot = Clock::get()?.slot;
 
     // Emit event
     emit!(SendTokenEvent {
@@ -125,52 +467,206 @@ pub fn send(
         amount,
         remote_bridge,
         remote_chain_selector,
-        remote_token
+        remote_token,
+        recipient,
+        remote_amount,
+        lamport_fee: sol_amount,
+        token_fee,
+        lp_fee,
+        referrer,
+        referral_fee,
+        nonce,
+        outbound_message: outbound_message.key()
     });
 
     Ok(())
 }
 
-pub fn message_receive(ctx: Context<MessageReceive>, token_id: String, source_chain_selector: u64, amount: u64) -> Result<()> {
+pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+    let referral = &mut ctx.accounts.referral;
+    referral.referrer = ctx.accounts.referrer.key();
+    referral.bump = ctx.bumps.referral;
+    Ok(())
+}
+
+pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> Result<()> {
+    let referral = &mut ctx.accounts.referral;
+    let amount = referral.claimable;
+    require!(amount > 0, BridgeErrorCode::InsufficientBalance);
+    referral.claimable = 0;
+
+    // The referral account is owned by the program, so it pays out directly
+    // and keeps its rent-exempt balance
+    referral.sub_lamports(amount)?;
+    ctx.accounts.referrer.add_lamports(amount)?;
+
+    emit!(ClaimReferralFeesEvent {
+        referrer: ctx.accounts.referrer.key(),
+        amount,
+    });
+
+    Ok(())
+}
+
+// Simulate `send` and return what it would charge and deliver
+#[allow(clippy::too_many_arguments)]
+pub fn quote_send(
+    ctx: Context<QuoteSend>,
+    amount: u64,
+    _remote_bridge: String,
+    _remote_chain_selector: u64,
+    _remote_token: [u8; 20],
+    recipient: [u8; 20],
+    max_fee_lamports: u64,
+    min_amount_out: u128,
+    referrer_bps: u16
+) -> Result<SendQuote> {
+    let accts = ctx.accounts;
+
+    require!(recipient != [0u8; 20], BridgeErrorCode::InvalidRecipient);
+
+    let token_route = &accts.token_route;
+    require!(token_route.local_token == accts.token_mint.key(), BridgeErrorCode::DisMatchToken);
+    token_route.check_send_open(&accts.bridge)?;
+
+    quote(
+        &accts.bridge,
+        token_route,
+        &accts.token_mint.to_account_info(),
+        &accts.fee_schedule,
+        accts.price_feed.as_ref(),
+        amount,
+        max_fee_lamports,
+        min_amount_out,
+        accts.referral.as_ref().map(|_| referrer_bps)
+    )
+}
+
+pub fn message_receive(
+    ctx: Context<MessageReceive>,
+    token_id: [u8; 32],
+    source_chain_selector: u64,
+    message_id: [u8; 32],
+    remote_amount: u128
+) -> Result<()> {
     let bridge = &ctx.accounts.bridge;
-    
-    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
 
-    // Check if token is supported
-    require!(bridge.token_ids.contains(&token_id.clone()), BridgeErrorCode::UnsupportedToken);
+    // Each (source_chain_selector, message_id) can only be delivered once
+    require!(!ctx.accounts.message_receipt.executed, BridgeErrorCode::MessageAlreadyProcessed);
 
-    // Check if token is supported
-    let token_address = bridge.get_token_address(token_id.clone()).ok_or(BridgeErrorCode::UnsupportedToken)?;
+    // Guardians attest the message; whoever submits it only pays the fees
+    let digest = bridge.message_digest(
+        source_chain_selector,
+        &message_id,
+        &token_id,
+        &ctx.accounts.user.key(),
+        remote_amount
+    );
+    bridge.verify_guardian_signatures(&ctx.accounts.instructions, &digest)?;
 
-    require!(token_address == &ctx.accounts.token_mint.key(), BridgeErrorCode::DisMatchToken);
+    let token_route = &mut ctx.accounts.token_route;
+    require!(token_route.local_token == ctx.accounts.token_mint.key(), BridgeErrorCode::DisMatchToken);
+    token_route.check_receive_open(bridge)?;
+    require!(token_route.remote_chain_selector == source_chain_selector, BridgeErrorCode::InvalidChainSelector);
 
-    let token_program = &ctx.accounts.token_program;
-    let bridge_token_account = &ctx.accounts.bridge_token_account;
-    let to_token_account = &ctx.accounts.user_token_account;
+    // The message carries the amount in the remote token's decimals
+    let amount = token_route.to_local_amount(remote_amount)?;
 
-    let balance = bridge_token_account.amount;
-    require!(amount <= balance, BridgeErrorCode::InsufficientBalance);
+    let token_program = &ctx.accounts.token_program;
+    let user = &ctx.accounts.user;
 
     let (_, bump) = Pubkey::find_program_address(&[BRIDGE_SEED], ctx.program_id);
     let vault_seeds = &[BRIDGE_SEED, &[bump]];
     let signer = &[&vault_seeds[..]];
 
-    // Transfer tokens from bridge to receiver
-    let cpi_accounts = Transfer {
-        from: bridge_token_account.to_account_info(),
-        to: to_token_account.to_account_info(),
-        authority: ctx.accounts.bridge.to_account_info(),
-    };
+    let (amount, lp_fee, to_address) = match token_route.mode {
+        RouteMode::LockRelease => {
+            // Only release what was locked for this route, less the liquidity
+            // fee kept in the vault for the route's providers
+            token_route.debit(amount)?;
+            let lp_fee = token_route.lp_fee(amount, token_route.receive_lp_fee_bps);
+            token_route.accrue_lp_fee(lp_fee)?;
+            let amount = amount - lp_fee;
 
-    let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
-    token::transfer(cpi_context.with_signer(signer), amount)?;
+            let to_token_account = ctx.accounts.user_token_account
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingRouteAccount)?;
+
+            let bridge_token_account = ctx.accounts.bridge_token_account
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingBridgeTokenAccount)?;
+
+            let balance = bridge_token_account.amount;
+            require!(amount <= balance, BridgeErrorCode::InsufficientBalance);
+
+            // Transfer tokens from bridge to receiver
+            let cpi_accounts = TransferChecked {
+                from: bridge_token_account.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: to_token_account.to_account_info(),
+                authority: ctx.accounts.bridge.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+            token_interface::transfer_checked(cpi_context.with_signer(signer), amount, ctx.accounts.token_mint.decimals)?;
+            (amount, lp_fee, to_token_account.key())
+        }
+        RouteMode::BurnMint => {
+            let to_token_account = ctx.accounts.user_token_account
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingRouteAccount)?;
 
+            // Mint the wrapped tokens to the receiver
+            let cpi_accounts = MintTo {
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: to_token_account.to_account_info(),
+                authority: ctx.accounts.bridge.to_account_info(),
+            };
+
+            let cpi_context = CpiContext::new(token_program.to_account_info(), cpi_accounts);
+            token_interface::mint_to(cpi_context.with_signer(signer), amount)?;
+            (amount, 0, to_token_account.key())
+        }
+        RouteMode::Native => {
+            // Only release what was locked for this route
+            token_route.debit(amount)?;
+
+            let sol_escrow = ctx.accounts.sol_escrow
+                .as_ref()
+                .ok_or(BridgeErrorCode::MissingRouteAccount)?;
+
+            let (_, escrow_bump) = Pubkey::find_program_address(&[SOL_ESCROW_SEED], ctx.program_id);
+
+            // Release lamports from the escrow to the receiver's system account
+            invoke_signed(
+                &system_instruction::transfer(&sol_escrow.key(), &user.key(), amount),
+                &[
+                    sol_escrow.clone(),
+                    user.clone(),
+                    ctx.accounts.system_program.to_account_info().clone(),
+                ],
+                &[&[SOL_ESCROW_SEED, &[escrow_bump]]],
+            )?;
+            (amount, 0, user.key())
+        }
+    };
+
+    let message_receipt = &mut ctx.accounts.message_receipt;
+    message_receipt.executed = true;
+    message_receipt.source_chain_selector = source_chain_selector;
+    message_receipt.message_id = message_id;
+    message_receipt.recipient = ctx.accounts.user.key();
+    message_receipt.amount = amount;
+    message_receipt.slot = Clock::get()?.slot;
 
     emit!(MessageReceivedEvent {
         source_chain_selector,
-        to_address: to_token_account.key(),
+        message_id,
+        to_address,
         token_id,
         amount,
+        remote_amount,
+        lp_fee,
     });
 
     Ok(())
@@ -178,6 +674,7 @@ pub fn message_receive(ctx: Context<MessageReceive>, token_id: String, source_ch
 
 
 #[derive(Accounts)]
+#[instruction(amount: u64, remote_chain_selector: u64, remote_token: [u8; 20])]
 pub struct AddLiquidity<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
@@ -185,15 +682,26 @@ pub struct AddLiquidity<'info> {
     #[account(
         mut,
         seeds = [BRIDGE_SEED],
-        bump
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
     )]
     pub bridge: Box<Account<'info, Bridge>>,
 
     #[account(mut)]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
 
     #[account(mut)]
-    pub token_account: Box<Account<'info, TokenAccount>>,
+    pub token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_ROUTE_SEED,
+            Bridge::get_evm_token_id(bridge.chain_selector, &token_mint.key(), remote_chain_selector, &remote_token).as_ref()
+        ],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
 
     #[account(
         init_if_needed,
@@ -201,24 +709,186 @@ pub struct AddLiquidity<'info> {
         seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
         bump,
         token::mint = token_mint,
-        token::authority = bridge
+        token::authority = bridge,
+        token::token_program = token_program
     )]
-    pub bridge_token_account: Box<Account<'info, TokenAccount>>,
+    pub bridge_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
 
-    pub token_program: Program<'info, Token>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [LP_MINT_SEED, token_route.token_id.as_ref()],
+        bump,
+        mint::decimals = token_mint.decimals,
+        mint::authority = bridge,
+        mint::token_program = token_program
+    )]
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = lp_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub lp_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [LP_POSITION_SEED, token_route.token_id.as_ref(), user.key().as_ref()],
+        bump,
+        space = 8 + LpPosition::INIT_SPACE
+    )]
+    pub lp_position: Box<Account<'info, LpPosition>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Send<'info> {
+#[instruction(shares: u64, remote_chain_selector: u64, remote_token: [u8; 20])]
+pub struct RemoveLiquidity<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
+    #[account(
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = user,
+        token::token_program = token_program
+    )]
+    pub token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_ROUTE_SEED,
+            Bridge::get_evm_token_id(bridge.chain_selector, &token_mint.key(), remote_chain_selector, &remote_token).as_ref()
+        ],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
+
     #[account(
         mut,
+        seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = bridge,
+        token::token_program = token_program
+    )]
+    pub bridge_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [LP_MINT_SEED, token_route.token_id.as_ref()],
+        bump
+    )]
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = lp_mint,
+        token::authority = user,
+        token::token_program = token_program
+    )]
+    pub lp_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(remote_chain_selector: u64, remote_token: [u8; 20])]
+pub struct ClaimLpFees<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
         seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = token_mint,
+        token::authority = user,
+        token::token_program = token_program
+    )]
+    pub token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_ROUTE_SEED,
+            Bridge::get_evm_token_id(bridge.chain_selector, &token_mint.key(), remote_chain_selector, &remote_token).as_ref()
+        ],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = bridge,
+        token::token_program = token_program
+    )]
+    pub bridge_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [LP_MINT_SEED, token_route.token_id.as_ref()],
         bump
     )]
+    pub lp_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        token::mint = lp_mint,
+        token::authority = user,
+        token::token_program = token_program
+    )]
+    pub lp_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [LP_POSITION_SEED, token_route.token_id.as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub lp_position: Box<Account<'info, LpPosition>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, remote_bridge: String, remote_chain_selector: u64, remote_token: [u8; 20])]
+pub struct Send<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
     pub bridge: Box<Account<'info, Bridge>>,
 
     /// CHECK:` doc comment explaining why no checks through types are necessary.
@@ -230,61 +900,245 @@ pub struct Send<'info> {
     pub vault: AccountInfo<'info>,
 
     #[account(mut)]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
 
+    // Not used by native SOL routes
     #[account(mut)]
-    pub token_account: Box<Account<'info, TokenAccount>>,
+    pub token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
 
+    #[account(
+        mut,
+        seeds = [
+            TOKEN_ROUTE_SEED,
+            Bridge::get_evm_token_id(bridge.chain_selector, &token_mint.key(), remote_chain_selector, &remote_token).as_ref()
+        ],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
+
+    /// CHECK: system owned PDA holding the lamports of native SOL routes.
+    #[account(
+        mut,
+        seeds = [SOL_ESCROW_SEED],
+        bump
+    )]
+    pub sol_escrow: Option<AccountInfo<'info>>,
+
+    // Only used by lock/release routes
     #[account(
         mut,
         seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
         bump,
         token::mint = token_mint,
-        token::authority = bridge
+        token::authority = bridge,
+        token::token_program = token_program
+    )]
+    pub bridge_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: FEE_SCHEDULE_SEED PDA of the route, read by FeeSchedule::load as it may not be initialized.
+    #[account(
+        seeds = [FEE_SCHEDULE_SEED, token_route.token_id.as_ref(), remote_chain_selector.to_be_bytes().as_ref()],
+        bump
     )]
-    pub bridge_token_account: Box<Account<'info, TokenAccount>>,
+    pub fee_schedule: AccountInfo<'info>,
 
-    pub token_program: Program<'info, Token>,
+    // Only needed when the fee schedule takes a token fee
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [FEE_COLLECTOR_SEED, token_mint.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = bridge,
+        token::token_program = token_program
+    )]
+    pub fee_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: SOL/USD price account, checked against bridge.price_feed by Bridge::protocol_fee_lamports.
+    /// Only needed when the route has no fee schedule and the protocol fee is set in USD.
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    // Referral account of the integrator the send came through, if any
+    #[account(
+        mut,
+        seeds = [REFERRAL_SEED, referral.referrer.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Box<Account<'info, Referral>>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [OUTBOUND_SEQUENCE_SEED, remote_chain_selector.to_be_bytes().as_ref()],
+        bump,
+        space = 8 + OutboundSequence::INIT_SPACE
+    )]
+    pub outbound_sequence: Box<Account<'info, OutboundSequence>>,
+
+    #[account(
+        init,
+        payer = user,
+        seeds = [
+            OUTBOUND_MESSAGE_SEED,
+            remote_chain_selector.to_be_bytes().as_ref(),
+            outbound_sequence.sequence.to_be_bytes().as_ref()
+        ],
+        bump,
+        space = 8 + OutboundMessage::INIT_SPACE
+    )]
+    pub outbound_message: Box<Account<'info, OutboundMessage>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct MessageReceive<'info> {
+pub struct RegisterReferrer<'info> {
     #[account(mut)]
-    pub owner: Signer<'info>,
+    pub referrer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = referrer,
+        seeds = [REFERRAL_SEED, referrer.key().as_ref()],
+        bump,
+        space = 8 + Referral::INIT_SPACE
+    )]
+    pub referral: Box<Account<'info, Referral>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralFees<'info> {
+    #[account(mut)]
+    pub referrer: Signer<'info>,
 
     #[account(
         mut,
+        seeds = [REFERRAL_SEED, referrer.key().as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Box<Account<'info, Referral>>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, remote_bridge: String, remote_chain_selector: u64, remote_token: [u8; 20])]
+pub struct QuoteSend<'info> {
+    #[account(
         seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        seeds = [
+            TOKEN_ROUTE_SEED,
+            Bridge::get_evm_token_id(bridge.chain_selector, &token_mint.key(), remote_chain_selector, &remote_token).as_ref()
+        ],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
+
+    /// CHECK: FEE_SCHEDULE_SEED PDA of the route, read by FeeSchedule::load as it may not be initialized.
+    #[account(
+        seeds = [FEE_SCHEDULE_SEED, token_route.token_id.as_ref(), remote_chain_selector.to_be_bytes().as_ref()],
         bump
     )]
+    pub fee_schedule: AccountInfo<'info>,
+
+    /// CHECK: SOL/USD price account, checked against bridge.price_feed by Bridge::protocol_fee_lamports.
+    /// Only needed when the route has no fee schedule and the protocol fee is set in USD.
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    // Referral account the send would go through, if any
+    #[account(
+        seeds = [REFERRAL_SEED, referral.referrer.as_ref()],
+        bump = referral.bump
+    )]
+    pub referral: Option<Box<Account<'info, Referral>>>,
+}
+
+#[derive(Accounts)]
+#[instruction(token_id: [u8; 32], source_chain_selector: u64, message_id: [u8; 32])]
+pub struct MessageReceive<'info> {
+    #[account(mut)]
+    pub relayer: Signer<'info>,
+
+    #[account(
+        seeds = [ROLE_SEED, Role::Relayer.seed().as_ref(), relayer.key().as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
     pub bridge: Box<Account<'info, Bridge>>,
 
+    #[account(
+        mut,
+        seeds = [TOKEN_ROUTE_SEED, token_id.as_ref()],
+        bump = token_route.bump
+    )]
+    pub token_route: Box<Account<'info, TokenRoute>>,
+
     #[account(mut)]
-    pub token_mint: Box<Account<'info, Mint>>,
+    pub token_mint: Box<InterfaceAccount<'info, Mint>>,
 
     /// CHECK:` doc comment explaining why no checks through types are necessary.
     #[account(mut)]
     pub user: AccountInfo<'info>,
 
+    // Not used by native SOL routes
     #[account(
         init_if_needed,
-        payer = owner,
+        payer = relayer,
         associated_token::mint = token_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program
+    )]
+    pub user_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    /// CHECK: system owned PDA holding the lamports of native SOL routes.
+    #[account(
+        mut,
+        seeds = [SOL_ESCROW_SEED],
+        bump
     )]
-    pub user_token_account: Box<Account<'info, TokenAccount>>,
+    pub sol_escrow: Option<AccountInfo<'info>>,
 
+    // Only used by lock/release routes
     #[account(
         mut,
         seeds = [BRIDGE_TOKEN_VAULT_SEED, token_mint.key().as_ref()],
         bump,
         token::mint = token_mint,
-        token::authority = bridge
+        token::authority = bridge,
+        token::token_program = token_program
+    )]
+    pub bridge_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+
+    #[account(
+        init_if_needed,
+        payer = relayer,
+        seeds = [MESSAGE_RECEIPT_SEED, source_chain_selector.to_be_bytes().as_ref(), message_id.as_ref()],
+        bump,
+        space = 8 + MessageReceipt::INIT_SPACE
     )]
-    pub bridge_token_account: Box<Account<'info, TokenAccount>>,
+    pub message_receipt: Box<Account<'info, MessageReceipt>>,
+
+    /// CHECK: instructions sysvar, used to read the guardians' Ed25519 signatures.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
  
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>
 }
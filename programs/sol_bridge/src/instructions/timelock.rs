@@ -0,0 +1,143 @@
+use anchor_lang::prelude::*;
+
+use crate::{state::*, constants::*, error::*, event::*};
+
+pub fn set_timelock_delay(ctx: Context<SetTimelockDelay>, timelock_delay: i64) -> Result<()> {
+    let bridge = &mut ctx.accounts.bridge;
+    require!(bridge.owner == *ctx.accounts.owner.key, BridgeErrorCode::InvalidOwner);
+    // The delay can only grow, otherwise it could be dropped right before an execution
+    require!(
+        timelock_delay >= bridge.timelock_delay
+            && (MIN_TIMELOCK_DELAY..=MAX_TIMELOCK_DELAY).contains(&timelock_delay),
+        BridgeErrorCode::InvalidTimelockDelay
+    );
+    bridge.timelock_delay = timelock_delay;
+
+    emit!(SetTimelockDelayEvent {
+        timelock_delay,
+    });
+
+    Ok(())
+}
+
+pub fn schedule_operation(ctx: Context<ScheduleOperation>, operation: Operation) -> Result<()> {
+    if let Operation::SetProtocolFee { protocol_fee, .. } = operation {
+        require!(protocol_fee != 0, BridgeErrorCode::InvalidProtocolFee);
+    }
+
+    let bridge = &mut ctx.accounts.bridge;
+    let nonce = bridge.operation_nonce;
+    bridge.operation_nonce = nonce.checked_add(1).ok_or(BridgeErrorCode::Overflow)?;
+
+    // Bridges initialized before the minimum existed still have no delay set
+    let eta = Clock::get()?
+        .unix_timestamp
+        .checked_add(bridge.timelock_delay.max(MIN_TIMELOCK_DELAY))
+        .ok_or(BridgeErrorCode::Overflow)?;
+
+    let queued_operation = &mut ctx.accounts.queued_operation;
+    queued_operation.nonce = nonce;
+    queued_operation.operation = operation;
+    queued_operation.scheduled_by = ctx.accounts.authority.key();
+    queued_operation.eta = eta;
+    queued_operation.bump = ctx.bumps.queued_operation;
+
+    emit!(ScheduleOperationEvent {
+        nonce,
+        operation,
+        scheduled_by: queued_operation.scheduled_by,
+        eta,
+    });
+
+    Ok(())
+}
+
+pub fn cancel_operation(ctx: Context<CancelOperation>, nonce: u64) -> Result<()> {
+    let guardian = ctx.accounts.guardian.key();
+    require!(
+        ctx.accounts.bridge.guardians.contains(&guardian),
+        BridgeErrorCode::InvalidGuardian
+    );
+
+    emit!(CancelOperationEvent {
+        nonce,
+        operation: ctx.accounts.queued_operation.operation,
+        guardian,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetTimelockDelay<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+}
+
+#[derive(Accounts)]
+#[instruction(operation: Operation)]
+pub struct ScheduleOperation<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [ROLE_SEED, operation.role().seed().as_ref(), authority.key().as_ref()],
+        bump = role_member.bump
+    )]
+    pub role_member: Box<Account<'info, RoleMember>>,
+
+    #[account(
+        mut,
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        init,
+        payer = authority,
+        seeds = [QUEUED_OPERATION_SEED, bridge.operation_nonce.to_be_bytes().as_ref()],
+        bump,
+        space = 8 + QueuedOperation::INIT_SPACE
+    )]
+    pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: u64)]
+pub struct CancelOperation<'info> {
+    pub guardian: Signer<'info>,
+
+    #[account(
+        seeds = [BRIDGE_SEED],
+        bump,
+        constraint = bridge.to_account_info().data_len() == BRIDGE_SPACE @ BridgeErrorCode::InvalidBridgeLayout
+    )]
+    pub bridge: Box<Account<'info, Bridge>>,
+
+    #[account(
+        mut,
+        close = scheduled_by,
+        seeds = [QUEUED_OPERATION_SEED, nonce.to_be_bytes().as_ref()],
+        bump = queued_operation.bump
+    )]
+    pub queued_operation: Box<Account<'info, QueuedOperation>>,
+
+    /// CHECK: receives the rent of the queued operation
+    #[account(
+        mut,
+        address = queued_operation.scheduled_by
+    )]
+    pub scheduled_by: AccountInfo<'info>,
+}
@@ -21,88 +21,280 @@ pub mod sol_bridge {
         instructions::initialize(ctx, protocol_fee, chain_selecotr)
     }
 
-    pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, protocol_fee: u64) -> Result<()> {
-        instructions::set_protocol_fee(ctx, protocol_fee)
+    pub fn migrate_bridge_layout(ctx: Context<MigrateBridgeLayout>) -> Result<()> {
+        instructions::migrate_bridge_layout(ctx)
     }
 
-    pub fn withdraw_token(ctx: Context<WithdrawToken>, token_id: String, amount: u64) -> Result<()> {
-        instructions::withdraw_token(ctx, token_id, amount)
+    pub fn set_timelock_delay(ctx: Context<SetTimelockDelay>, timelock_delay: i64) -> Result<()> {
+        instructions::set_timelock_delay(ctx, timelock_delay)
     }
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        instructions::withdraw(ctx, amount)
+    pub fn schedule_operation(ctx: Context<ScheduleOperation>, operation: Operation) -> Result<()> {
+        instructions::schedule_operation(ctx, operation)
+    }
+
+    pub fn propose_owner(ctx: Context<ProposeOwner>, pending_owner: Pubkey) -> Result<()> {
+        instructions::propose_owner(ctx, pending_owner)
+    }
+
+    pub fn accept_owner(ctx: Context<AcceptOwner>) -> Result<()> {
+        instructions::accept_owner(ctx)
+    }
+
+    pub fn cancel_owner_proposal(ctx: Context<CancelOwnerProposal>) -> Result<()> {
+        instructions::cancel_owner_proposal(ctx)
+    }
+
+    pub fn grant_role(ctx: Context<GrantRole>, role: Role, member: Pubkey) -> Result<()> {
+        instructions::grant_role(ctx, role, member)
+    }
+
+    pub fn revoke_role(ctx: Context<RevokeRole>, role: Role, member: Pubkey) -> Result<()> {
+        instructions::revoke_role(ctx, role, member)
+    }
+
+    pub fn pause(ctx: Context<Pause>) -> Result<()> {
+        instructions::pause(ctx)
+    }
+
+    pub fn unpause(ctx: Context<Unpause>) -> Result<()> {
+        instructions::unpause(ctx)
+    }
+
+    pub fn set_max_referrer_bps(ctx: Context<SetMaxReferrerBps>, max_referrer_bps: u16) -> Result<()> {
+        instructions::set_max_referrer_bps(ctx, max_referrer_bps)
+    }
+
+    pub fn set_price_feed(ctx: Context<SetPriceFeed>, nonce: u64) -> Result<()> {
+        instructions::set_price_feed(ctx, nonce)
+    }
+
+    pub fn set_guardians(ctx: Context<SetGuardians>, guardians: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        instructions::set_guardians(ctx, guardians, threshold)
+    }
+
+
+    pub fn withdraw_token_fees(ctx: Context<WithdrawTokenFees>, amount: u64) -> Result<()> {
+        instructions::withdraw_token_fees(ctx, amount)
+    }
+
+
+    pub fn set_fee_recipients(ctx: Context<SetFeeRecipients>, fee_recipients: Vec<FeeRecipient>) -> Result<()> {
+        instructions::set_fee_recipients(ctx, fee_recipients)
     }
 
     pub fn add_token(
-        ctx: Context<ManageToken>, 
+        ctx: Context<AddToken>, 
         local_token: Pubkey, 
         remote_chain_selector: u64, 
-        remote_token: String
+        remote_token: [u8; 20],
+        mode: RouteMode,
+        remote_decimals: u8
     ) -> Result<()> {
         instructions::add_token(
             ctx, 
             local_token, 
             remote_chain_selector,
-            remote_token
+            remote_token,
+            mode,
+            remote_decimals
         )
     }
 
-    pub fn remove_token(
-        ctx: Context<ManageToken>, 
-        local_token: Pubkey, 
-        remote_chain_selector: u64, 
-        remote_token: String
+    pub fn create_wrapped_token(
+        ctx: Context<CreateWrappedToken>,
+        remote_chain_selector: u64,
+        remote_token: [u8; 20],
+        remote_decimals: u8,
+        name: String,
+        symbol: String,
+        uri: String
     ) -> Result<()> {
-        instructions::remove_token(
-            ctx, 
-            local_token, 
-            remote_chain_selector, 
-            remote_token
+        instructions::create_wrapped_token(
+            ctx,
+            remote_chain_selector,
+            remote_token,
+            remote_decimals,
+            name,
+            symbol,
+            uri
         )
     }
-  
-    pub fn add_liquidity(
-        ctx: Context<AddLiquidity>, 
-        amount: u64, 
-        remote_chain_selector: u64, 
-        remote_token: String
+
+    pub fn set_lp_fee(ctx: Context<SetLpFee>, token_id: [u8; 32], nonce: u64) -> Result<()> {
+        instructions::set_lp_fee(ctx, token_id, nonce)
+    }
+
+    pub fn pause_route(ctx: Context<PauseRoute>, token_id: [u8; 32], send: bool, receive: bool) -> Result<()> {
+        instructions::pause_route(ctx, token_id, send, receive)
+    }
+
+    pub fn unpause_route(ctx: Context<UnpauseRoute>, token_id: [u8; 32], send: bool, receive: bool) -> Result<()> {
+        instructions::unpause_route(ctx, token_id, send, receive)
+    }
+
+    pub fn set_fee_schedule(
+        ctx: Context<SetFeeSchedule>,
+        token_id: [u8; 32],
+        remote_chain_selector: u64,
+        nonce: u64
     ) -> Result<()> {
-        instructions::add_liquidity(
-            ctx, 
-            amount, 
-            remote_chain_selector,
-            remote_token
-        )
+        instructions::set_fee_schedule(ctx, token_id, remote_chain_selector, nonce)
     }
 
+    pub fn migrate_token_ids(ctx: Context<ManageToken>) -> Result<()> {
+        instructions::migrate_token_ids(ctx)
+    }
+
+    pub fn migrate_token_route(
+        ctx: Context<MigrateTokenRoute>,
+        token_id: [u8; 32],
+        remote_decimals: u8,
+        target_balance: u64
+    ) -> Result<()> {
+        instructions::migrate_token_route(ctx, token_id, remote_decimals, target_balance)
+    }
+
+    // guardian function
+    pub fn cancel_operation(ctx: Context<CancelOperation>, nonce: u64) -> Result<()> {
+        instructions::cancel_operation(ctx, nonce)
+    }
+
+    // relayer function
     pub fn message_receive(
         ctx: Context<MessageReceive>, 
-        token_id: String, 
+        token_id: [u8; 32], 
         source_chain_selector: u64, 
-        amount: u64
+        message_id: [u8; 32],
+        amount: u128
     ) -> Result<()> {
         instructions::message_receive(
             ctx, 
             token_id, 
             source_chain_selector, 
+            message_id,
             amount
         )
     }
 
     //  user function
+    #[allow(clippy::too_many_arguments)]
     pub fn send(ctx: Context<Send>, 
         amount: u64, 
         remote_bridge: String,
         remote_chain_selector: u64, 
-        remote_token: String
+        remote_token: [u8; 20],
+        recipient: [u8; 20],
+        max_fee_lamports: u64,
+        min_amount_out: u128,
+        referrer_bps: u16
     ) -> Result<()> {
         instructions::send(
             ctx, 
             amount, 
             remote_bridge, 
             remote_chain_selector,
+            remote_token,
+            recipient,
+            max_fee_lamports,
+            min_amount_out,
+            referrer_bps
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn quote_send(
+        ctx: Context<QuoteSend>,
+        amount: u64,
+        remote_bridge: String,
+        remote_chain_selector: u64,
+        remote_token: [u8; 20],
+        recipient: [u8; 20],
+        max_fee_lamports: u64,
+        min_amount_out: u128,
+        referrer_bps: u16
+    ) -> Result<SendQuote> {
+        instructions::quote_send(
+            ctx,
+            amount,
+            remote_bridge,
+            remote_chain_selector,
+            remote_token,
+            recipient,
+            max_fee_lamports,
+            min_amount_out,
+            referrer_bps
+        )
+    }
+
+    // timelocked operations, executable by anyone once their delay has passed
+    pub fn set_protocol_fee(ctx: Context<SetProtocolFee>, nonce: u64) -> Result<()> {
+        instructions::set_protocol_fee(ctx, nonce)
+    }
+
+    pub fn withdraw(ctx: Context<Withdraw>, nonce: u64) -> Result<()> {
+        instructions::withdraw(ctx, nonce)
+    }
+
+    pub fn withdraw_token(ctx: Context<WithdrawToken>, token_id: [u8; 32], nonce: u64) -> Result<()> {
+        instructions::withdraw_token(ctx, token_id, nonce)
+    }
+
+    pub fn remove_token(ctx: Context<RemoveToken>, token_id: [u8; 32], nonce: u64) -> Result<()> {
+        instructions::remove_token(ctx, token_id, nonce)
+    }
+
+    // fee distribution, callable by anyone
+    pub fn distribute_fees<'info>(ctx: Context<'_, '_, '_, 'info, DistributeFees<'info>>) -> Result<()> {
+        instructions::distribute_fees(ctx)
+    }
+
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        instructions::register_referrer(ctx)
+    }
+
+    pub fn claim_referral_fees(ctx: Context<ClaimReferralFees>) -> Result<()> {
+        instructions::claim_referral_fees(ctx)
+    }
+
+    // liquidity provider functions
+    pub fn add_liquidity(
+        ctx: Context<AddLiquidity>, 
+        amount: u64, 
+        remote_chain_selector: u64, 
+        remote_token: [u8; 20]
+    ) -> Result<()> {
+        instructions::add_liquidity(
+            ctx, 
+            amount, 
+            remote_chain_selector,
+            remote_token
+        )
+    }
+
+    pub fn remove_liquidity(
+        ctx: Context<RemoveLiquidity>,
+        shares: u64,
+        remote_chain_selector: u64,
+        remote_token: [u8; 20]
+    ) -> Result<()> {
+        instructions::remove_liquidity(
+            ctx,
+            shares,
+            remote_chain_selector,
+            remote_token
+        )
+    }
+
+    pub fn claim_lp_fees(
+        ctx: Context<ClaimLpFees>,
+        remote_chain_selector: u64,
+        remote_token: [u8; 20]
+    ) -> Result<()> {
+        instructions::claim_lp_fees(
+            ctx,
+            remote_chain_selector,
             remote_token
         )
     }
-    
 }
@@ -1,160 +1,375 @@
 use anchor_lang::prelude::*;
+use solana_program::{
+    ed25519_program,
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
 use tiny_keccak::{Hasher, Keccak};
-use hex::encode;
-use std::cmp::Ordering;
 
-use crate::error::BridgeErrorCode;
+use crate::{constants::*, error::BridgeErrorCode, state::PriceFeed};
+
+// Receives `weight` parts of the protocol fees paid out by distribute_fees
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct FeeRecipient {
+    pub recipient: Pubkey,
+    pub weight: u16,
+}
 
 #[account]
 #[derive(Default)]
 pub struct Bridge {
     pub owner: Pubkey,
     pub vault: Pubkey,
+    // Lamports, charged until a price feed and protocol_fee_usd_cents are configured
     pub protocol_fee: u64,
     pub chain_selector: u64,
+    // Legacy route table, drained into TokenRoute accounts by migrate_token_route
     pub token_ids: Vec<String>,
     pub token_addresses: Vec<Pubkey>,
     pub target_token_addresses: Vec<String>,
     pub target_balances: Vec<u64>,
     pub target_chain_selectors: Vec<u64>,
+    pub guardians: Vec<Pubkey>,
+    pub guardian_threshold: u8,
+    pub token_id_version: u8,
+    // Pyth-style SOL/USD price account and the limits a price must meet
+    pub price_feed: Pubkey,
+    pub max_price_age: u64,
+    pub max_price_conf_bps: u16,
+    pub fee_recipients: Vec<FeeRecipient>,
+    // Cap on the part of the lamport fee a send can pass to its referrer
+    pub max_referrer_bps: u16,
+    // Proposed by propose_owner, becomes the owner once it signs accept_owner
+    pub pending_owner: Pubkey,
+    // Emergency stop for send and message_receive on every route
+    pub paused: bool,
+    // Seconds between schedule_operation and the earliest execution
+    pub timelock_delay: i64,
+    // Nonce of the next queued operation
+    pub operation_nonce: u64,
+    // USD cents, charged in lamports at the price_feed SOL/USD price instead of
+    // protocol_fee once both are set
+    pub protocol_fee_usd_cents: u64,
+    // Bumped on every guardian set change, so signatures approving one change
+    // cannot be replayed later
+    pub guardian_set_index: u32,
+}
+
+// Layout written by the program before the guardian set was added. Removing a route
+// shrank the Vecs without clearing the bytes after them, so nothing past
+// target_chain_selectors can be trusted in an account written with it
+#[derive(AnchorDeserialize)]
+pub struct LegacyBridge {
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub protocol_fee: u64,
+    pub chain_selector: u64,
+    pub token_ids: Vec<String>,
+    pub token_addresses: Vec<Pubkey>,
+    pub target_token_addresses: Vec<String>,
+    pub target_balances: Vec<u64>,
+    pub target_chain_selectors: Vec<u64>,
+}
+
+impl LegacyBridge {
+    // The legacy routes keep their token ids until migrate_token_ids, every field added
+    // since starts from its default
+    pub fn into_bridge(self) -> Bridge {
+        Bridge {
+            owner: self.owner,
+            vault: self.vault,
+            protocol_fee: self.protocol_fee,
+            chain_selector: self.chain_selector,
+            token_ids: self.token_ids,
+            token_addresses: self.token_addresses,
+            target_token_addresses: self.target_token_addresses,
+            target_balances: self.target_balances,
+            target_chain_selectors: self.target_chain_selectors,
+            token_id_version: TOKEN_ID_VERSION_LEGACY,
+            timelock_delay: MIN_TIMELOCK_DELAY,
+            ..Default::default()
+        }
+    }
 }
 
 impl Bridge {
     // Helper function to perform keccak256 hashing
-    fn keccak256(data: &[u8]) -> String {
+    fn keccak256(data: &[u8]) -> [u8; 32] {
         let mut hasher = Keccak::v256();
         let mut output = [0u8; 32];
         hasher.update(data);
         hasher.finalize(&mut output);
-        // Convert the output (byte array) to a hex string
-        encode(output)
+        output
     }
 
-    pub fn get_token_id(
-        &self,
-        local_token: &[u8],        // Local token address (on Solana)
-        chain_selector: u64,       // Solana chain selector (uint64)
-        remote_chain_selector: u64,// EVM chain selector (uint64)
-        remote_token: &[u8],       // Remote token address (on EVM)
-    ) -> Result<String> {          // Token ID (hex string)
-    
-        // Compare keccak256 of local_token and remote_token
-        let local_hash = Self::keccak256(local_token);
-        let remote_hash = Self::keccak256(remote_token);
-    
-        // Determine the order based on keccak256 hashes
-        let token_id_input = match local_hash.cmp(&remote_hash) {
-            Ordering::Less => {
-                // If localToken hash is smaller, order: chainSelector, localToken, remoteChainSelector, remoteToken
-                [
-                    chain_selector.to_le_bytes().to_vec(), // Convert to Vec<u8>
-                    local_token.to_vec(),
-                    remote_chain_selector.to_le_bytes().to_vec(),
-                    remote_token.to_vec()
-                ].concat()
-            }
-            _ => {
-                // Otherwise, order: remoteChainSelector, remoteToken, chainSelector, localToken
-                [
-                    remote_chain_selector.to_le_bytes().to_vec(),
-                    remote_token.to_vec(),
-                    chain_selector.to_le_bytes().to_vec(),
-                    local_token.to_vec()
-                ].concat()
-            }
-        };
-    
-        // Compute the keccak256 of the concatenated result
-        let token_id_hash = Self::keccak256(&token_id_input);
-    
-        // Convert the keccak256 hash (likely a byte array) to a hex string
-        Ok(hex::encode(token_id_hash)) // `hex` crate for hex encoding
+    // EVM compatible token id, matches Solidity
+    // keccak256(abi.encodePacked(uint64 chainSelector, bytes32 localToken, uint64 remoteChainSelector, address remoteToken))
+    pub fn get_evm_token_id(
+        chain_selector: u64,
+        local_token: &Pubkey,
+        remote_chain_selector: u64,
+        remote_token: &[u8; 20],
+    ) -> [u8; 32] {
+        let token_id_input = [
+            chain_selector.to_be_bytes().as_ref(),
+            local_token.as_ref(),
+            remote_chain_selector.to_be_bytes().as_ref(),
+            remote_token.as_ref(),
+        ]
+        .concat();
+
+        Self::keccak256(&token_id_input)
     }
-    
-    pub fn add_token(
-        &mut self,
-        local_token: Pubkey,        // Local token address (on Solana)
-        remote_chain_selector: u64, // EVM chain selector (uint64)
-        remote_token: String,       // Remote token address (on EVM)
-    ) -> Result<String> {
-        // Encode local_token as bytes
-        let binding = local_token.to_string();
-        let local_token_bytes = binding.as_bytes();
-
-        // Compute the token ID using get_token_id function
-        let token_id = Self::get_token_id(
-            &self,
-            local_token_bytes,       // Local token as bytes
-            self.chain_selector,      // Solana chain selector (from Bridge struct)
-            remote_chain_selector,    // EVM chain selector
-            remote_token.as_bytes()   // Remote token as bytes
-        )?;
-
-        // Check if token ID already exists in the token_ids vector
-        if let Some(index) = self.token_ids.iter().position(|id| id == &token_id) {
-            // Check if the chain selector matches
-            if self.target_chain_selectors[index] == remote_chain_selector {
-                return Err(BridgeErrorCode::AlreadyExist.into()); // Token already registered
-            }
+
+    // Parse a "0x" prefixed (or bare) hex EVM address
+    pub fn parse_evm_address(address: &str) -> Result<[u8; 20]> {
+        let digits = address
+            .strip_prefix("0x")
+            .or_else(|| address.strip_prefix("0X"))
+            .unwrap_or(address);
+
+        hex::decode(digits)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(BridgeErrorCode::InvalidRemoteToken.into())
+    }
+
+    // Re-key every registered route from the legacy scheme to the EVM compatible one.
+    // Returns (local token, old token id, new token id) for each migrated route.
+    pub fn migrate_token_ids(&mut self) -> Result<Vec<(Pubkey, String, String)>> {
+        require!(
+            self.token_id_version == TOKEN_ID_VERSION_LEGACY,
+            BridgeErrorCode::InvalidTokenIdVersion
+        );
+
+        let mut migrated = Vec::with_capacity(self.token_ids.len());
+        for index in 0..self.token_ids.len() {
+            let token_id = hex::encode(Self::get_evm_token_id(
+                self.chain_selector,
+                &self.token_addresses[index],
+                self.target_chain_selectors[index],
+                &Self::parse_evm_address(&self.target_token_addresses[index])?
+            ));
+            let old_token_id = std::mem::replace(&mut self.token_ids[index], token_id.clone());
+            migrated.push((self.token_addresses[index], old_token_id, token_id));
         }
 
-        // Add the token if it doesn't already exist
-        self.token_ids.push(token_id.clone());
-        self.token_addresses.push(local_token);
-        self.target_token_addresses.push(remote_token);
-        self.target_balances.push(0); // Initialize balance with 0
-        self.target_chain_selectors.push(remote_chain_selector); // Store the chain selector
-        
-        Ok(token_id)
+        self.token_id_version = TOKEN_ID_VERSION_EVM;
+        Ok(migrated)
     }
 
-    pub fn remove_token(
-        &mut self,
-        local_token: Pubkey,        // Local token address (on Solana)
-        remote_chain_selector: u64, // EVM chain selector
-        remote_token: String,       // Remote token address (on EVM)
-    ) -> Result<String> {
-       // Encode local_token as bytes
-       let binding = local_token.to_string();
-       let local_token_bytes = binding.as_bytes();
-
-
-        // Compute the token ID using get_token_id function
-        let token_id = Self::get_token_id(
-            &self,
-            local_token_bytes,       
-            self.chain_selector,      // Solana chain selector
-            remote_chain_selector,    
-            remote_token.as_bytes()   // Remote token as bytes
-        )?;
-
-        // Find the index of the token ID in token_ids
-        if let Some(index) = self.token_ids.iter().position(|id| id == &token_id) {
-            if self.target_chain_selectors[index] == remote_chain_selector {
-                // Remove token info if found
-                self.token_ids.remove(index);
-                self.token_addresses.remove(index);
-                self.target_balances.remove(index);
-                self.target_token_addresses.remove(index);
-                self.target_chain_selectors.remove(index);
-                Ok(token_id)
-            } else {
-                Err(BridgeErrorCode::UnsupportedToken.into()) // Chain selector mismatch
-            }
-        } else {
-            Err(BridgeErrorCode::UnsupportedToken.into()) // Token ID not found
+    // Remove a route from the legacy route table so it can be moved into its own TokenRoute account.
+    // Returns (local token, remote chain selector, remote token). The legacy target balances were
+    // never updated, so the migrated balance is supplied by migrate_token_route instead.
+    pub fn take_legacy_route(&mut self, token_id: &[u8; 32]) -> Result<(Pubkey, u64, [u8; 20])> {
+        require!(
+            self.token_id_version == TOKEN_ID_VERSION_EVM,
+            BridgeErrorCode::InvalidTokenIdVersion
+        );
+
+        let index = self.token_ids.iter()
+            .position(|id| id == &hex::encode(token_id))
+            .ok_or(BridgeErrorCode::UnsupportedToken)?;
+        let remote_token = Self::parse_evm_address(&self.target_token_addresses[index])?;
+
+        let route = (
+            self.token_addresses.remove(index),
+            self.target_chain_selectors.remove(index),
+            remote_token,
+        );
+        self.token_ids.remove(index);
+        self.target_balances.remove(index);
+        self.target_token_addresses.remove(index);
+
+        Ok(route)
+    }
+
+    pub fn set_price_feed(&mut self, price_feed: Pubkey, max_price_age: u64, max_price_conf_bps: u16) -> Result<()> {
+        require!(
+            price_feed != Pubkey::default() && max_price_age > 0 && max_price_conf_bps as u64 <= BPS_DENOMINATOR,
+            BridgeErrorCode::InvalidPriceFeed
+        );
+
+        self.price_feed = price_feed;
+        self.max_price_age = max_price_age;
+        self.max_price_conf_bps = max_price_conf_bps;
+        Ok(())
+    }
+
+    // The protocol fee in lamports, converted from protocol_fee_usd_cents at the current
+    // SOL/USD price, or the flat protocol_fee while no USD fee or price feed is configured
+    pub fn protocol_fee_lamports(&self, price_feed: Option<&AccountInfo>) -> Result<u64> {
+        if self.protocol_fee_usd_cents == 0 || self.price_feed == Pubkey::default() {
+            return Ok(self.protocol_fee);
+        }
+
+        let price_feed = price_feed.ok_or(BridgeErrorCode::InvalidPriceFeed)?;
+        require_keys_eq!(price_feed.key(), self.price_feed, BridgeErrorCode::InvalidPriceFeed);
+
+        let price = PriceFeed::load(price_feed)?;
+        price.check(Clock::get()?.unix_timestamp, self.max_price_age, self.max_price_conf_bps)?;
+        price.usd_cents_to_lamports(self.protocol_fee_usd_cents)
+    }
+
+    pub fn set_fee_recipients(&mut self, fee_recipients: Vec<FeeRecipient>) -> Result<()> {
+        require!(
+            !fee_recipients.is_empty() && fee_recipients.len() <= MAX_FEE_RECIPIENTS,
+            BridgeErrorCode::InvalidFeeRecipients
+        );
+
+        for (index, fee_recipient) in fee_recipients.iter().enumerate() {
+            require!(fee_recipient.weight > 0, BridgeErrorCode::InvalidFeeRecipients);
+            require!(
+                !fee_recipients[..index].iter().any(|other| other.recipient == fee_recipient.recipient),
+                BridgeErrorCode::InvalidFeeRecipients
+            );
         }
+
+        self.fee_recipients = fee_recipients;
+        Ok(())
+    }
+
+    // Split `amount` between the fee recipients by weight, rounding down
+    pub fn fee_shares(&self, amount: u64) -> Vec<u64> {
+        let total_weight: u128 = self.fee_recipients.iter().map(|fee_recipient| fee_recipient.weight as u128).sum();
+        self.fee_recipients
+            .iter()
+            .map(|fee_recipient| (amount as u128 * fee_recipient.weight as u128 / total_weight) as u64)
+            .collect()
     }
 
-    // Get Token Address
-    pub fn get_token_address(
+    pub fn set_guardians(&mut self, guardians: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        require!(
+            !guardians.is_empty() && guardians.len() <= MAX_GUARDIANS,
+            BridgeErrorCode::InvalidGuardianSet
+        );
+        require!(
+            threshold > 0 && threshold as usize <= guardians.len(),
+            BridgeErrorCode::InvalidGuardianSet
+        );
+
+        // Reject duplicated keys so one guardian cannot count twice toward the threshold
+        for (index, guardian) in guardians.iter().enumerate() {
+            require!(!guardians[..index].contains(guardian), BridgeErrorCode::InvalidGuardianSet);
+        }
+
+        self.guardians = guardians;
+        self.guardian_threshold = threshold;
+        self.guardian_set_index = self.guardian_set_index.checked_add(1).ok_or(BridgeErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    // Digest the current guardians sign to hand over to a new set:
+    // keccak256(program_id, chain_selector, guardian_set_index, threshold, guardians)
+    pub fn guardian_set_digest(&self, guardians: &[Pubkey], threshold: u8) -> [u8; 32] {
+        let mut message = [
+            crate::ID.as_ref(),
+            &self.chain_selector.to_be_bytes(),
+            &self.guardian_set_index.to_be_bytes(),
+            &[threshold],
+        ]
+        .concat();
+        for guardian in guardians {
+            message.extend_from_slice(guardian.as_ref());
+        }
+
+        Self::keccak256(&message)
+    }
+
+    // Digest the guardians sign for an inbound message:
+    // keccak256(program_id, source_chain_selector, chain_selector, message_id, token_id, recipient, amount)
+    // where amount is in the remote token's decimals
+    pub fn message_digest(
         &self,
-        token_id: String,   
-    ) -> Option<&Pubkey> {
-        // Find the index of the token ID in token_ids
-        self.token_ids.iter()
-            .position(|id| id == &token_id)
-            .map(|index| &self.token_addresses[index])
+        source_chain_selector: u64,
+        message_id: &[u8; 32],
+        token_id: &[u8; 32],
+        recipient: &Pubkey,
+        amount: u128,
+    ) -> [u8; 32] {
+        let message = [
+            crate::ID.as_ref(),
+            &source_chain_selector.to_be_bytes(),
+            &self.chain_selector.to_be_bytes(),
+            message_id,
+            token_id,
+            recipient.as_ref(),
+            &[0u8; 16],               // amount is signed as a uint256
+            &amount.to_be_bytes(),
+        ]
+        .concat();
+
+        Self::keccak256(&message)
     }
 
-}
\ No newline at end of file
+    // Count distinct guardians that signed `digest` through Ed25519 program
+    // instructions placed before the current instruction.
+    pub fn verify_guardian_signatures(&self, instructions: &AccountInfo, digest: &[u8; 32]) -> Result<()> {
+        require!(self.guardian_threshold > 0, BridgeErrorCode::InvalidGuardianSet);
+
+        let current_index = load_current_index_checked(instructions)?;
+        let mut signers: Vec<Pubkey> = Vec::new();
+
+        for index in 0..current_index {
+            let instruction = load_instruction_at_checked(index as usize, instructions)?;
+            if instruction.program_id != ed25519_program::ID {
+                continue;
+            }
+
+            for (signer, message) in Self::parse_ed25519_instruction(&instruction.data)? {
+                if message == digest
+                    && self.guardians.contains(&signer)
+                    && !signers.contains(&signer)
+                {
+                    signers.push(signer);
+                }
+            }
+        }
+
+        require!(
+            signers.len() >= self.guardian_threshold as usize,
+            BridgeErrorCode::InsufficientGuardianSignatures
+        );
+        Ok(())
+    }
+
+    // Extract (public key, message) pairs from Ed25519 program instruction data.
+    // Only self-contained entries are accepted, so the bytes read here are the
+    // exact bytes the precompile verified.
+    fn parse_ed25519_instruction(data: &[u8]) -> Result<Vec<(Pubkey, &[u8])>> {
+        const OFFSETS_START: usize = 2;
+        const OFFSETS_SIZE: usize = 14;
+
+        let count = *data.first().ok_or(BridgeErrorCode::InvalidGuardianSignature)? as usize;
+        let mut entries = Vec::with_capacity(count);
+
+        for i in 0..count {
+            let start = OFFSETS_START + i * OFFSETS_SIZE;
+            let offsets = data
+                .get(start..start + OFFSETS_SIZE)
+                .ok_or(BridgeErrorCode::InvalidGuardianSignature)?;
+            let read = |at: usize| u16::from_le_bytes([offsets[at], offsets[at + 1]]);
+
+            // signature, public key and message must all live in this instruction
+            require!(
+                read(2) == u16::MAX && read(6) == u16::MAX && read(12) == u16::MAX,
+                BridgeErrorCode::InvalidGuardianSignature
+            );
+
+            let public_key_offset = read(4) as usize;
+            let message_offset = read(8) as usize;
+            let message_size = read(10) as usize;
+
+            let public_key: [u8; 32] = data
+                .get(public_key_offset..public_key_offset + 32)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or(BridgeErrorCode::InvalidGuardianSignature)?;
+            let message = data
+                .get(message_offset..message_offset + message_size)
+                .ok_or(BridgeErrorCode::InvalidGuardianSignature)?;
+
+            entries.push((Pubkey::new_from_array(public_key), message));
+        }
+
+        Ok(entries)
+    }
+}
@@ -0,0 +1,101 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, error::BridgeErrorCode};
+
+// Fee settings of a FeeSchedule, as passed to set_fee_schedule
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, Default, InitSpace)]
+pub struct FeeScheduleParams {
+    // Lamports paid into the VAULT_SEED account, replaces the global protocol fee
+    pub flat_fee: u64,
+    // Fee taken in the bridged token, in basis points of the amount
+    pub fee_bps: u16,
+    // Bounds of the token fee, a max_fee of 0 leaves it uncapped
+    pub min_fee: u64,
+    pub max_fee: u64,
+    // Charge neither the flat nor the token fee
+    pub exempt: bool,
+}
+
+// Fees charged by `send` on one (token_id, remote_chain_selector)
+#[account]
+#[derive(Default, InitSpace)]
+pub struct FeeSchedule {
+    pub token_id: [u8; 32],
+    pub remote_chain_selector: u64,
+    pub params: FeeScheduleParams,
+    pub bump: u8,
+}
+
+// What `send` charges on top of the liquidity fee
+pub struct SendFees {
+    // Lamports paid into the VAULT_SEED account
+    pub lamport_fee: u64,
+    pub token_fee: u64,
+}
+
+// Return data of quote_send
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SendQuote {
+    pub lamport_fee: u64,
+    // Part of lamport_fee that goes to the referrer instead of the vault
+    pub referral_fee: u64,
+    pub token_fee: u64,
+    // Withheld by Token-2022 mints with a transfer fee on the way into the vault
+    pub transfer_fee: u64,
+    pub lp_fee: u64,
+    // Left after every fee but below what the remote token can represent, kept by the sender
+    pub dust: u64,
+    // Local amount bridged after fees, and what the recipient gets in the remote token's decimals
+    pub amount: u64,
+    pub remote_amount: u128,
+    // Tokens locked for the route on Solana; the remote chain's own liquidity
+    // is not visible to this program
+    pub available_liquidity: u64,
+}
+
+impl FeeSchedule {
+    pub fn set(&mut self, token_id: [u8; 32], remote_chain_selector: u64, params: FeeScheduleParams, bump: u8) -> Result<()> {
+        require!(params.fee_bps <= MAX_TOKEN_FEE_BPS, BridgeErrorCode::InvalidFeeSchedule);
+        require!(params.max_fee == 0 || params.min_fee <= params.max_fee, BridgeErrorCode::InvalidFeeSchedule);
+
+        self.token_id = token_id;
+        self.remote_chain_selector = remote_chain_selector;
+        self.params = params;
+        self.bump = bump;
+        Ok(())
+    }
+
+    // The schedule lives at a fixed PDA that may not exist yet, routes without
+    // one pay the global protocol_fee
+    pub fn load(info: &AccountInfo) -> Result<Option<FeeSchedule>> {
+        if info.data_is_empty() {
+            return Ok(None);
+        }
+        require_keys_eq!(*info.owner, crate::ID, BridgeErrorCode::InvalidFeeSchedule);
+
+        let data = info.try_borrow_data()?;
+        Ok(Some(FeeSchedule::try_deserialize(&mut &data[..])?))
+    }
+
+    // Token fee on `amount`, clamped to [min_fee, max_fee] and never more than `amount`
+    pub fn token_fee(&self, amount: u64) -> u64 {
+        let params = &self.params;
+        if params.exempt {
+            return 0;
+        }
+
+        let mut fee = (amount as u128 * params.fee_bps as u128 / BPS_DENOMINATOR as u128) as u64;
+        fee = fee.max(params.min_fee);
+        if params.max_fee != 0 {
+            fee = fee.min(params.max_fee);
+        }
+        fee.min(amount)
+    }
+
+    pub fn send_fees(&self, amount: u64) -> SendFees {
+        SendFees {
+            lamport_fee: if self.params.exempt { 0 } else { self.params.flat_fee },
+            token_fee: self.token_fee(amount),
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, error::BridgeErrorCode};
+
+// Where a provider's liquidity fee claims start from in one route. The shares
+// themselves are the provider's LP tokens, so the position only decides how
+// much claim_lp_fees pays out, never who owns the fees
+#[account]
+#[derive(Default, InitSpace)]
+pub struct LpPosition {
+    pub provider: Pubkey,
+    pub token_id: [u8; 32],
+    // TokenRoute::acc_fee_per_share the claimable fees are counted from
+    pub fee_checkpoint: u128,
+}
+
+impl LpPosition {
+    // Fees earned by `shares` LP tokens since the checkpoint. A checkpoint ahead of
+    // the accumulator was taken on a route that has since been removed and added again
+    pub fn earned(&self, shares: u64, acc_fee_per_share: u128) -> Result<u64> {
+        let per_share = acc_fee_per_share.saturating_sub(self.fee_checkpoint);
+        let earned = (shares as u128).checked_mul(per_share).ok_or(BridgeErrorCode::Overflow)? / LP_FEE_PRECISION;
+        u64::try_from(earned).map_err(|_| BridgeErrorCode::Overflow.into())
+    }
+
+    // Move the checkpoint so the grown balance keeps what the old balance had
+    // earned, without counting fees from before the deposit on the new shares
+    pub fn deposit(&mut self, shares_before: u64, shares_after: u64, acc_fee_per_share: u128) -> Result<()> {
+        let earned = self.earned(shares_before, acc_fee_per_share)? as u128;
+        let per_share = earned
+            .checked_mul(LP_FEE_PRECISION)
+            .ok_or(BridgeErrorCode::Overflow)?
+            / shares_after as u128;
+
+        self.fee_checkpoint = acc_fee_per_share - per_share;
+        Ok(())
+    }
+
+    pub fn claim(&mut self, acc_fee_per_share: u128) {
+        self.fee_checkpoint = acc_fee_per_share;
+    }
+}
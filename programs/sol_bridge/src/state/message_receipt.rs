@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+// One receipt per inbound message, keyed by (source_chain_selector, message_id)
+#[account]
+#[derive(Default, InitSpace)]
+pub struct MessageReceipt {
+    pub executed: bool,
+    pub source_chain_selector: u64,
+    pub message_id: [u8; 32],
+    pub recipient: Pubkey,
+    pub amount: u64,
+    pub slot: u64,
+}
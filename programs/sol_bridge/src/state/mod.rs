@@ -1,3 +1,21 @@
 pub mod bridge;
+pub mod fee_schedule;
+pub mod lp_position;
+pub mod message_receipt;
+pub mod outbound_message;
+pub mod price_feed;
+pub mod queued_operation;
+pub mod referral;
+pub mod role;
+pub mod token_route;
 
 pub use bridge::*;
+pub use fee_schedule::*;
+pub use lp_position::*;
+pub use message_receipt::*;
+pub use outbound_message::*;
+pub use price_feed::*;
+pub use queued_operation::*;
+pub use referral::*;
+pub use role::*;
+pub use token_route::*;
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+// Next outbound nonce for one destination chain selector
+#[account]
+#[derive(Default, InitSpace)]
+pub struct OutboundSequence {
+    pub remote_chain_selector: u64,
+    pub sequence: u64,
+}
+
+// Persistent record of a `send`, keyed by (remote_chain_selector, nonce)
+#[account]
+#[derive(Default, InitSpace)]
+pub struct OutboundMessage {
+    pub nonce: u64,
+    pub remote_chain_selector: u64,
+    pub sender: Pubkey,
+    pub recipient: [u8; 20],
+    pub token_id: [u8; 32],
+    pub amount: u64,
+    // amount in the remote token's decimals
+    pub remote_amount: u128,
+    pub fee: u64,
+    pub slot: u64,
+}
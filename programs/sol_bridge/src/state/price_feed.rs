@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+
+use crate::error::BridgeErrorCode;
+
+// Offsets into a Pyth price account, see pyth_sdk_solana::state::SolanaPriceAccount
+const MAGIC_OFFSET: usize = 0;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPO_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+const PRICE_ACCOUNT_LEN: usize = 240;
+
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const ACCOUNT_TYPE_PRICE: u32 = 3;
+const PRICE_STATUS_TRADING: u32 = 1;
+
+// Aggregate price read from a Pyth-style price account, price * 10^expo
+pub struct PriceFeed {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub timestamp: i64,
+}
+
+impl PriceFeed {
+    fn read<const N: usize>(data: &[u8], offset: usize) -> [u8; N] {
+        data[offset..offset + N].try_into().unwrap()
+    }
+
+    pub fn load(info: &AccountInfo) -> Result<PriceFeed> {
+        let data = info.try_borrow_data()?;
+        require!(data.len() >= PRICE_ACCOUNT_LEN, BridgeErrorCode::InvalidPriceFeed);
+        require!(
+            u32::from_le_bytes(Self::read(&data, MAGIC_OFFSET)) == PYTH_MAGIC
+                && u32::from_le_bytes(Self::read(&data, ACCOUNT_TYPE_OFFSET)) == ACCOUNT_TYPE_PRICE
+                && u32::from_le_bytes(Self::read(&data, AGG_STATUS_OFFSET)) == PRICE_STATUS_TRADING,
+            BridgeErrorCode::InvalidPriceFeed
+        );
+
+        Ok(PriceFeed {
+            price: i64::from_le_bytes(Self::read(&data, AGG_PRICE_OFFSET)),
+            conf: u64::from_le_bytes(Self::read(&data, AGG_CONF_OFFSET)),
+            expo: i32::from_le_bytes(Self::read(&data, EXPO_OFFSET)),
+            timestamp: i64::from_le_bytes(Self::read(&data, TIMESTAMP_OFFSET)),
+        })
+    }
+
+    // Reject prices older than `max_age` seconds or with a confidence interval
+    // wider than `max_conf_bps` of the price
+    pub fn check(&self, now: i64, max_age: u64, max_conf_bps: u16) -> Result<()> {
+        require!(self.price > 0, BridgeErrorCode::InvalidPriceFeed);
+        require!(
+            now.saturating_sub(self.timestamp) <= max_age as i64,
+            BridgeErrorCode::InvalidPriceFeed
+        );
+        require!(
+            self.conf as u128 * 10_000 <= self.price as u128 * max_conf_bps as u128,
+            BridgeErrorCode::InvalidPriceFeed
+        );
+        Ok(())
+    }
+
+    // Lamports worth `usd_cents` at a SOL/USD price
+    pub fn usd_cents_to_lamports(&self, usd_cents: u64) -> Result<u64> {
+        // lamports = usd_cents / 100 * 10^9 / (price * 10^expo)
+        let mut numerator = usd_cents as u128 * 10_000_000;
+        let mut denominator = self.price as u128;
+        let scale = 10u128
+            .checked_pow(self.expo.unsigned_abs())
+            .ok_or(BridgeErrorCode::InvalidPriceFeed)?;
+        if self.expo < 0 {
+            numerator = numerator.checked_mul(scale).ok_or(BridgeErrorCode::Overflow)?;
+        } else {
+            denominator = denominator.checked_mul(scale).ok_or(BridgeErrorCode::Overflow)?;
+        }
+
+        u64::try_from(numerator / denominator).map_err(|_| BridgeErrorCode::Overflow.into())
+    }
+}
@@ -0,0 +1,52 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::BridgeErrorCode, state::{FeeScheduleParams, Role}};
+
+// Admin actions that only take effect once their timelock expires
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum Operation {
+    Withdraw { beneficiary: Pubkey, amount: u64 },
+    WithdrawToken { token_id: [u8; 32], beneficiary: Pubkey, amount: u64 },
+    RemoveToken { token_id: [u8; 32] },
+    SetProtocolFee { protocol_fee: u64, protocol_fee_usd_cents: u64 },
+    SetLpFee { token_id: [u8; 32], send_fee_bps: u16, receive_fee_bps: u16 },
+    SetFeeSchedule { token_id: [u8; 32], remote_chain_selector: u64, params: FeeScheduleParams },
+    SetPriceFeed { price_feed: Pubkey, max_price_age: u64, max_price_conf_bps: u16 },
+}
+
+impl Operation {
+    // Role allowed to schedule the operation
+    pub fn role(&self) -> Role {
+        match self {
+            Operation::Withdraw { .. } | Operation::WithdrawToken { .. } => Role::Treasurer,
+            Operation::RemoveToken { .. } => Role::TokenRegistrar,
+            Operation::SetProtocolFee { .. }
+            | Operation::SetLpFee { .. }
+            | Operation::SetFeeSchedule { .. }
+            | Operation::SetPriceFeed { .. } => Role::FeeManager,
+        }
+    }
+}
+
+// Scheduled by schedule_operation, executable by anyone from `eta` and
+// cancellable by a guardian until then
+#[account]
+#[derive(InitSpace)]
+pub struct QueuedOperation {
+    pub nonce: u64,
+    pub operation: Operation,
+    pub scheduled_by: Pubkey,
+    pub eta: i64,
+    pub bump: u8,
+}
+
+impl QueuedOperation {
+    // The queued operation, once its timelock has expired
+    pub fn ready(&self) -> Result<Operation> {
+        require!(
+            Clock::get()?.unix_timestamp >= self.eta,
+            BridgeErrorCode::TimelockNotExpired
+        );
+        Ok(self.operation)
+    }
+}
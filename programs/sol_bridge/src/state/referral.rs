@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::*, error::BridgeErrorCode};
+
+// Lamports earned by an integrator that refers `send` calls, held by this account
+// until claim_referral_fees
+#[account]
+#[derive(Default, InitSpace)]
+pub struct Referral {
+    pub referrer: Pubkey,
+    pub claimable: u64,
+    pub bump: u8,
+}
+
+impl Referral {
+    // Referrer's part of the lamport fee, at most the bridge-wide max_referrer_bps
+    pub fn referral_fee(lamport_fee: u64, referrer_bps: u16, max_referrer_bps: u16) -> Result<u64> {
+        require!(referrer_bps <= max_referrer_bps, BridgeErrorCode::InvalidReferrerFee);
+        Ok((lamport_fee as u128 * referrer_bps as u128 / BPS_DENOMINATOR as u128) as u64)
+    }
+
+    pub fn credit(&mut self, amount: u64) -> Result<()> {
+        self.claimable = self.claimable.checked_add(amount).ok_or(BridgeErrorCode::Overflow)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+
+// Privileged operations are split between roles so each ops key can only do its own job
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug, InitSpace)]
+pub enum Role {
+    // delivers guardian attested messages with message_receive
+    Relayer,
+    // sets the protocol fee, fee schedules, liquidity fees and fee recipients
+    FeeManager,
+    // adds, wraps, migrates and removes token routes
+    TokenRegistrar,
+    // pauses the bridge and its routes
+    Pauser,
+    // withdraws protocol fees and route balances
+    Treasurer,
+}
+
+impl Role {
+    pub fn seed(self) -> [u8; 1] {
+        [self as u8]
+    }
+}
+
+// Grants `role` to `member`, one account per (role, member) and closed on revoke
+#[account]
+#[derive(InitSpace)]
+pub struct RoleMember {
+    pub role: Role,
+    pub member: Pubkey,
+    pub bump: u8,
+}
@@ -0,0 +1,276 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+    state::Mint,
+};
+
+use crate::{constants::*, error::BridgeErrorCode, state::Bridge};
+
+// How a route moves tokens on the Solana side
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum RouteMode {
+    // send locks tokens in the bridge vault, message_receive releases them
+    #[default]
+    LockRelease,
+    // the Bridge PDA is mint authority: send burns, message_receive mints
+    BurnMint,
+    // native SOL, send locks lamports in the SOL_ESCROW_SEED account, message_receive releases them
+    Native,
+}
+
+// One bridgeable (local token, remote chain, remote token) route, seeded by its token id
+#[account]
+#[derive(Default, InitSpace)]
+pub struct TokenRoute {
+    pub token_id: [u8; 32],
+    pub local_token: Pubkey,
+    pub remote_chain_selector: u64,
+    pub remote_token: [u8; 20],
+    // Tokens locked in the bridge vault for this route, so routes sharing a mint
+    // cannot pay out each other's liquidity
+    pub target_balance: u64,
+    pub bump: u8,
+    pub mode: RouteMode,
+    pub local_decimals: u8,
+    pub remote_decimals: u8,
+    // Part of target_balance supplied by liquidity providers and the liquidity
+    // fees they earned, owned pro rata by holders of the route's LP_MINT_SEED mint
+    pub liquidity: u64,
+    // LP shares outstanding, the supply of the route's LP mint
+    pub lp_shares: u64,
+    // Liquidity fee in basis points, charged on lock/release routes with providers
+    pub send_lp_fee_bps: u16,
+    pub receive_lp_fee_bps: u16,
+    // Liquidity fees earned per LP share, scaled by LP_FEE_PRECISION
+    pub acc_fee_per_share: u128,
+    // Per direction emergency stops, on top of bridge.paused
+    pub send_paused: bool,
+    pub receive_paused: bool,
+}
+
+impl TokenRoute {
+    pub fn register(
+        &mut self,
+        token_id: [u8; 32],
+        local_token: Pubkey,
+        remote_chain_selector: u64,
+        remote_token: [u8; 20],
+        mode: RouteMode,
+        bump: u8,
+    ) -> Result<()> {
+        require!(self.token_id != token_id, BridgeErrorCode::AlreadyExist); // Token already registered
+
+        self.token_id = token_id;
+        self.local_token = local_token;
+        self.remote_chain_selector = remote_chain_selector;
+        self.remote_token = remote_token;
+        self.target_balance = 0;
+        self.bump = bump;
+        self.mode = mode;
+        Ok(())
+    }
+
+    // Closing the route would strand whatever it still has locked in the vault
+    pub fn check_empty(&self) -> Result<()> {
+        require!(
+            self.target_balance == 0 && self.liquidity == 0 && self.lp_shares == 0,
+            BridgeErrorCode::RouteNotEmpty
+        );
+        Ok(())
+    }
+
+    pub fn check_send_open(&self, bridge: &Bridge) -> Result<()> {
+        require!(!bridge.paused, BridgeErrorCode::BridgePaused);
+        require!(!self.send_paused, BridgeErrorCode::SendPaused);
+        Ok(())
+    }
+
+    pub fn check_receive_open(&self, bridge: &Bridge) -> Result<()> {
+        require!(!bridge.paused, BridgeErrorCode::BridgePaused);
+        require!(!self.receive_paused, BridgeErrorCode::ReceivePaused);
+        Ok(())
+    }
+
+    pub fn set_decimals(&mut self, local_decimals: u8, remote_decimals: u8) -> Result<()> {
+        require!(
+            local_decimals <= MAX_TOKEN_DECIMALS && remote_decimals <= MAX_TOKEN_DECIMALS,
+            BridgeErrorCode::InvalidDecimals
+        );
+
+        self.local_decimals = local_decimals;
+        self.remote_decimals = remote_decimals;
+        Ok(())
+    }
+
+    // Round a local amount down to the precision both chains can represent.
+    // The remainder is dust that never leaves the sender.
+    pub fn truncate_dust(&self, amount: u64) -> u64 {
+        if self.local_decimals > self.remote_decimals {
+            // Gaps up to MAX_TOKEN_DECIMALS overflow u64 but not u128, a scale
+            // above u64::MAX makes the whole amount dust
+            let scale = 10u128.pow((self.local_decimals - self.remote_decimals) as u32);
+            (amount as u128 - amount as u128 % scale) as u64
+        } else {
+            amount
+        }
+    }
+
+    // Scale a local amount to the remote token's decimals
+    pub fn to_remote_amount(&self, amount: u64) -> Result<u128> {
+        let amount = amount as u128;
+        if self.remote_decimals >= self.local_decimals {
+            amount
+                .checked_mul(10u128.pow((self.remote_decimals - self.local_decimals) as u32))
+                .ok_or(BridgeErrorCode::Overflow.into())
+        } else {
+            Ok(amount / 10u128.pow((self.local_decimals - self.remote_decimals) as u32))
+        }
+    }
+
+    // Scale a remote amount down to the local mint's decimals, rounding down
+    pub fn to_local_amount(&self, remote_amount: u128) -> Result<u64> {
+        let amount = if self.remote_decimals >= self.local_decimals {
+            remote_amount / 10u128.pow((self.remote_decimals - self.local_decimals) as u32)
+        } else {
+            remote_amount
+                .checked_mul(10u128.pow((self.local_decimals - self.remote_decimals) as u32))
+                .ok_or(BridgeErrorCode::Overflow)?
+        };
+
+        u64::try_from(amount).map_err(|_| BridgeErrorCode::Overflow.into())
+    }
+
+    // Token-2022 mints whose extensions would let someone else move, freeze or
+    // block the tokens held in custody cannot be bridged
+    pub fn check_mint_extensions(mint: &AccountInfo) -> Result<()> {
+        if *mint.owner != spl_token_2022::ID {
+            return Ok(());
+        }
+
+        let data = mint.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        for extension in mint.get_extension_types()? {
+            require!(
+                !matches!(
+                    extension,
+                    ExtensionType::PermanentDelegate
+                        | ExtensionType::NonTransferable
+                        | ExtensionType::TransferHook
+                ),
+                BridgeErrorCode::UnsupportedMintExtension
+            );
+        }
+        Ok(())
+    }
+
+    // Token-2022 transfer fee withheld from a transfer of `amount` in the current epoch
+    pub fn transfer_fee(mint: &AccountInfo, amount: u64) -> Result<u64> {
+        if *mint.owner != spl_token_2022::ID {
+            return Ok(0);
+        }
+
+        let data = mint.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        match mint.get_extension::<TransferFeeConfig>() {
+            Ok(transfer_fee_config) => transfer_fee_config
+                .calculate_epoch_fee(Clock::get()?.epoch, amount)
+                .ok_or(BridgeErrorCode::Overflow.into()),
+            Err(_) => Ok(0),
+        }
+    }
+
+    // LP tokens burned outside the bridge leave the mint supply below lp_shares,
+    // their value goes to the remaining holders
+    pub fn sync_lp_shares(&mut self, lp_supply: u64) {
+        self.lp_shares = lp_supply;
+    }
+
+    // Add provider liquidity and return the LP shares to mint for it
+    pub fn deposit_liquidity(&mut self, amount: u64) -> Result<u64> {
+        let shares = if self.lp_shares == 0 || self.liquidity == 0 {
+            amount
+        } else {
+            u64::try_from(amount as u128 * self.lp_shares as u128 / self.liquidity as u128)
+                .map_err(|_| BridgeErrorCode::Overflow)?
+        };
+        require!(shares > 0, BridgeErrorCode::AmountTooSmall);
+
+        self.liquidity = self.liquidity.checked_add(amount).ok_or(BridgeErrorCode::Overflow)?;
+        self.lp_shares = self.lp_shares.checked_add(shares).ok_or(BridgeErrorCode::Overflow)?;
+        self.credit(amount)?;
+        Ok(shares)
+    }
+
+    // Burn LP shares and return the tokens they are worth, bounded by what is
+    // currently locked for the route
+    pub fn withdraw_liquidity(&mut self, shares: u64) -> Result<u64> {
+        require!(shares > 0 && shares <= self.lp_shares, BridgeErrorCode::InsufficientBalance);
+
+        let amount = (shares as u128 * self.liquidity as u128 / self.lp_shares as u128) as u64;
+        require!(amount > 0, BridgeErrorCode::AmountTooSmall);
+        require!(amount <= self.target_balance, BridgeErrorCode::InsufficientBalance);
+
+        self.liquidity = self.liquidity.checked_sub(amount).ok_or(BridgeErrorCode::Underflow)?;
+        self.lp_shares -= shares;
+        self.debit(amount)?;
+        Ok(amount)
+    }
+
+    pub fn set_lp_fee(&mut self, send_fee_bps: u16, receive_fee_bps: u16) -> Result<()> {
+        require!(
+            send_fee_bps <= MAX_LP_FEE_BPS && receive_fee_bps <= MAX_LP_FEE_BPS,
+            BridgeErrorCode::InvalidLpFee
+        );
+
+        self.send_lp_fee_bps = send_fee_bps;
+        self.receive_lp_fee_bps = receive_fee_bps;
+        Ok(())
+    }
+
+    // Liquidity fee on `amount`, nothing while the route has no providers to pay
+    pub fn lp_fee(&self, amount: u64, fee_bps: u16) -> u64 {
+        if self.lp_shares == 0 {
+            return 0;
+        }
+        (amount as u128 * fee_bps as u128 / BPS_DENOMINATOR as u128) as u64
+    }
+
+    // Add a fee already held in the vault to the providers' liquidity, so every
+    // LP share is worth more and the fee follows whoever holds the shares
+    pub fn accrue_lp_fee(&mut self, fee: u64) -> Result<()> {
+        if fee == 0 {
+            return Ok(());
+        }
+
+        let per_share = fee as u128 * LP_FEE_PRECISION / self.lp_shares as u128;
+        self.acc_fee_per_share = self.acc_fee_per_share
+            .checked_add(per_share)
+            .ok_or(BridgeErrorCode::Overflow)?;
+        self.liquidity = self.liquidity.checked_add(fee).ok_or(BridgeErrorCode::Overflow)?;
+        self.credit(fee)
+    }
+
+    // LP shares worth at least `amount`, rounded up so claims never take more
+    // than the burned shares are worth
+    pub fn shares_for(&self, amount: u64) -> Result<u64> {
+        require!(self.liquidity > 0, BridgeErrorCode::InsufficientBalance);
+
+        let shares = (amount as u128 * self.lp_shares as u128).div_ceil(self.liquidity as u128);
+        u64::try_from(shares).map_err(|_| BridgeErrorCode::Overflow.into())
+    }
+
+    pub fn credit(&mut self, amount: u64) -> Result<()> {
+        self.target_balance = self.target_balance
+            .checked_add(amount)
+            .ok_or(BridgeErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    pub fn debit(&mut self, amount: u64) -> Result<()> {
+        self.target_balance = self.target_balance
+            .checked_sub(amount)
+            .ok_or(BridgeErrorCode::Underflow)?;
+        Ok(())
+    }
+}
@@ -2,9 +2,10 @@ import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { SolBridge } from "../target/types/sol_bridge";
 
-import { TOKEN_PROGRAM_ID, createAccount, createInitializeMintInstruction, MINT_SIZE, getMinimumBalanceForRentExemptMint, createMint, createAssociatedTokenAccount, getAssociatedTokenAddress, ASSOCIATED_TOKEN_PROGRAM_ID, mintTo, mintToChecked, getAccount, getMint, getAssociatedTokenAddressSync, createAssociatedTokenAccountInstruction, createMintToCheckedInstruction } from "@solana/spl-token";
+import { TOKEN_PROGRAM_ID, NATIVE_MINT, createAccount, createInitializeMintInstruction, MINT_SIZE, getMinimumBalanceForRentExemptMint, createMint, createAssociatedTokenAccount, getAssociatedTokenAddress, ASSOCIATED_TOKEN_PROGRAM_ID, mintTo, mintToChecked, getAccount, getMint, getAssociatedTokenAddressSync, createAssociatedTokenAccountInstruction, createMintToCheckedInstruction, TOKEN_2022_PROGRAM_ID, ExtensionType, getMintLen, createInitializeTransferFeeConfigInstruction, createInitializePermanentDelegateInstruction } from "@solana/spl-token";
 import * as bs58 from "bs58";
-import { SystemProgram, Keypair, PublicKey, Transaction, SYSVAR_RENT_PUBKEY, SYSVAR_CLOCK_PUBKEY, Connection, clusterApiUrl, sendAndConfirmTransaction } from "@solana/web3.js";
+import { SystemProgram, Keypair, PublicKey, Transaction, SYSVAR_RENT_PUBKEY, SYSVAR_CLOCK_PUBKEY, SYSVAR_INSTRUCTIONS_PUBKEY, Ed25519Program, Connection, clusterApiUrl, sendAndConfirmTransaction, TransactionInstruction } from "@solana/web3.js";
+import { keccak_256 } from "@noble/hashes/sha3";
 import assert from "assert";
 import {
   PythSolanaReceiver,
@@ -16,7 +17,8 @@ import { Wallet } from '@project-serum/anchor';
 
 describe("sol_bridge", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.SolBridge as Program<SolBridge>;
 
@@ -28,10 +30,201 @@ describe("sol_bridge", () => {
 
   let user = Keypair.fromSecretKey(bs58.decode(""));
 
+  // test guardian set, replace with the real guardian keys in your product
+  // derived from fixed seeds so that reruns find the guardian set they configured before
+  const guardians = [0, 1, 2].map((i) => Keypair.fromSeed(keccak_256(`test guardian ${i}`)));
+  const guardianThreshold = 2;
+
   type Event = anchor.IdlEvents<typeof program["idl"]>;
 
   let chainSelector = 1601511254; // test value, you can modify value in your product
 
+  // mock SOL/USD price account loaded from tests/fixtures, use the Pyth SOL/USD price account in your product
+  const priceFeed = new PublicKey("CDghCg2s3QznE111orBTnqnwrmEAsiiVyAPXDXVpxTt");
+
+  const evmAddress = (address: string) => [...Buffer.from(address.slice(2), "hex")];
+
+  // keccak256(abi.encodePacked(uint64 chainSelector, bytes32 localToken, uint64 remoteChainSelector, address remoteToken))
+  const getTokenId = (localToken: PublicKey, remoteChainSelector: number, remoteToken: string) =>
+    Buffer.from(keccak_256(Buffer.concat([
+      new anchor.BN(chainSelector).toArrayLike(Buffer, "be", 8),
+      localToken.toBuffer(),
+      new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+      Buffer.from(evmAddress(remoteToken)),
+    ])));
+
+  const getTokenRoute = (tokenId: Buffer) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("TOKEN_ROUTE_SEED"),
+        tokenId
+      ],
+      program.programId
+    )[0];
+
+  // the route's LP share mint, minted by addLiquidity and burned by removeLiquidity and claimLpFees
+  const getLpMint = (tokenId: Buffer) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("LP_MINT_SEED"),
+        tokenId
+      ],
+      program.programId
+    )[0];
+
+  // role is an anchor enum value such as { relayer: {} }, its seed is the variant index
+  const roles = ["relayer", "feeManager", "tokenRegistrar", "pauser", "treasurer"];
+  const getRoleMember = (role: object, member: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("ROLE_SEED"),
+        Buffer.from([roles.indexOf(Object.keys(role)[0])]),
+        member.toBuffer()
+      ],
+      program.programId
+    )[0];
+
+  const getQueuedOperation = (nonce: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("QUEUED_OPERATION_SEED"),
+        nonce.toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    )[0];
+
+  // queues a timelocked operation, scheduled by an owner account that holds `role`
+  const scheduleOperation = async (operation: object, role: object) => {
+    const nonce = (await program.account.bridge.fetch(bridge)).operationNonce;
+    const tx = await program.rpc.scheduleOperation(
+      operation,
+      {
+        accounts: {
+          authority: owner.publicKey,
+          roleMember: getRoleMember(role, owner.publicKey),
+          bridge,
+          queuedOperation: getQueuedOperation(nonce),
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
+    return nonce;
+  };
+
+  // one Ed25519 verify instruction per guardian, placed before the instruction that checks them
+  const guardianSignatures = (digest: Buffer, signers: Keypair[]) =>
+    signers.map((guardian) =>
+      Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: guardian.secretKey,
+        message: digest,
+      })
+    );
+
+  // keccak256(program_id, chain_selector, guardian_set_index, threshold, guardians)
+  const guardianSetDigest = (guardianSetIndex: number, newGuardians: PublicKey[], threshold: number) =>
+    Buffer.from(keccak_256(Buffer.concat([
+      program.programId.toBuffer(),
+      new anchor.BN(chainSelector).toArrayLike(Buffer, "be", 8),
+      new anchor.BN(guardianSetIndex).toArrayLike(Buffer, "be", 4),
+      Buffer.from([threshold]),
+      ...newGuardians.map((guardian) => guardian.toBuffer()),
+    ])));
+
+  // keccak256(program_id, src_chain, chain, message_id, token_id, recipient, amount as uint256)
+  const messageDigest = (remoteChainSelector: number, messageId: Buffer, tokenId: Buffer, recipient: PublicKey, remoteAmount: anchor.BN) =>
+    Buffer.from(keccak_256(Buffer.concat([
+      program.programId.toBuffer(),
+      new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+      new anchor.BN(chainSelector).toArrayLike(Buffer, "be", 8),
+      messageId,
+      tokenId,
+      recipient.toBuffer(),
+      remoteAmount.toArrayLike(Buffer, "be", 32),
+    ])));
+
+  // relays a message for `user`, attested by the given guardian signature instructions
+  const receiveMessage = (
+    localToken: PublicKey,
+    remoteChainSelector: number,
+    tokenId: Buffer,
+    messageId: Buffer,
+    remoteAmount: anchor.BN,
+    preInstructions: TransactionInstruction[]
+  ) =>
+    program.rpc.messageReceive(
+      [...tokenId],
+      new anchor.BN(remoteChainSelector),
+      [...messageId],
+      remoteAmount,
+      {
+        accounts: {
+          relayer: owner.publicKey,
+          roleMember: getRoleMember({ relayer: {} }, owner.publicKey),
+          bridge,
+          tokenRoute: getTokenRoute(tokenId),
+          tokenMint: localToken,
+          user: user.publicKey,
+          userTokenAccount: getAssociatedTokenAddressSync(localToken, user.publicKey),
+          bridgeTokenAccount: PublicKey.findProgramAddressSync(
+            [Buffer.from("BRIDGE_TOKEN_VAULT_SEED"), localToken.toBuffer()],
+            program.programId
+          )[0],
+          messageReceipt: PublicKey.findProgramAddressSync(
+            [
+              Buffer.from("MESSAGE_RECEIPT_SEED"),
+              new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+              messageId
+            ],
+            program.programId
+          )[0],
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId
+        },
+        preInstructions,
+        signers: [owner]
+      }
+    );
+
+  // reads an event emitted by a confirmed transaction from its logs
+  const getEvent = async (tx: string, name: string) => {
+    await provider.connection.confirmTransaction(tx, "confirmed");
+    const transaction = await provider.connection.getTransaction(tx, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+    const eventParser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+    const event = [...eventParser.parseLogs(transaction.meta.logMessages)].find((event) => event.name === name);
+    assert.ok(event, `${name} not emitted`);
+    return event.data as any;
+  };
+
+  const expectInsufficientGuardianSignatures = (execute: Promise<string>) =>
+    assert.rejects(execute, (error: any) => error.error.errorCode.code === "InsufficientGuardianSignatures");
+
+  // the bridge timelock delay is at least a day, so executing right after scheduling must fail
+  const expectTimelockNotExpired = (execute: Promise<string>) =>
+    assert.rejects(execute, (error: any) => error.error.errorCode.code === "TimelockNotExpired");
+
+  const getFeeSchedule = (tokenId: Buffer, remoteChainSelector: number) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("FEE_SCHEDULE_SEED"),
+        tokenId,
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    )[0];
+
+  const getFeeTokenAccount = (localToken: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("FEE_COLLECTOR_SEED"),
+        localToken.toBuffer()
+      ],
+      program.programId
+    )[0];
+
   it("Get PDA", async() => {
     [bridge, bridgeBump] = await anchor.web3.PublicKey.findProgramAddress(
       [
@@ -48,6 +241,86 @@ describe("sol_bridge", () => {
     );
   });
   
+  it("migrate the bridge layout", async() => {
+    // bridges created by the legacy program are 5000 bytes and are rewritten once into the
+    // BRIDGE_SPACE layout, before any other instruction accepts them
+    const bridgeSpace = 6 * 1024;
+    const bridgeAccount = await provider.connection.getAccountInfo(bridge);
+    if (!bridgeAccount || bridgeAccount.data.length == bridgeSpace) {
+      return;
+    }
+
+    const tx = await program.rpc.migrateBridgeLayout({
+      accounts: {
+        owner: owner.publicKey,
+        bridge,
+        systemProgram: SystemProgram.programId
+      },
+      signers: [owner]
+    });
+    console.log("tx->", tx);
+
+    // the fields added after the legacy route table start from their defaults
+    assert.equal((await provider.connection.getAccountInfo(bridge)).data.length, bridgeSpace);
+    const bridgeData = await program.account.bridge.fetch(bridge);
+    assert.equal(bridgeData.tokenIdVersion, 0);
+    assert.equal(bridgeData.guardians.length, 0);
+    assert.ok(bridgeData.pendingOwner.equals(PublicKey.default));
+  });
+
+  it("Is initialized!", async () => {
+    // an existing bridge, legacy ones included, is migrated above instead
+    if (await provider.connection.getAccountInfo(bridge)) {
+      return;
+    }
+    const protocolFee = 10000000; // 0.01 Sol, charged until a USD fee and price feed are set
+    // owner must be the upgrade authority of the deployed program
+    const programData = PublicKey.findProgramAddressSync(
+      [program.programId.toBuffer()],
+      new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    )[0];
+    const tx = await program.rpc.initialize(
+      new anchor.BN(protocolFee),
+      new anchor.BN(chainSelector),
+      {
+        accounts: {
+          bridge,
+          owner: owner.publicKey,
+          vault,
+          program: program.programId,
+          programData,
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
+  });
+
+  it("grant roles", async() => {
+    // a single test key holds every role, use separate keys for each role in your product
+    for (const role of roles) {
+      const roleMember = getRoleMember({ [role]: {} }, owner.publicKey);
+      if (await program.account.roleMember.fetchNullable(roleMember)) {
+        continue;
+      }
+      const tx = await program.rpc.grantRole(
+        { [role]: {} },
+        owner.publicKey,
+        {
+          accounts: {
+            owner: owner.publicKey,
+            bridge,
+            roleMember,
+            systemProgram: SystemProgram.programId
+          },
+          signers: [owner]
+        }
+      );
+      console.log("tx->", tx);
+    }
+  });
+
   it("Get Token ID", async() => {
     [bridge, bridgeBump] = await anchor.web3.PublicKey.findProgramAddress(
       [
@@ -73,20 +346,149 @@ describe("sol_bridge", () => {
         console.log(tokenId)
       }
     }
+
+    console.log("evm token id->", getTokenId(localToken, remoteChainSelector, remoteToken).toString("hex"));
   });
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const protocolFee = 100;
-    const tx = await program.rpc.initialize(
-      new anchor.BN(protocolFee),
-      new anchor.BN(chainSelector),
+  it("migrate token ids to the evm compatible scheme", async() => {
+    // only bridges still on the legacy scheme have ids to migrate
+    if ((await program.account.bridge.fetch(bridge)).tokenIdVersion != 0) {
+      return;
+    }
+    const tx = await program.rpc.migrateTokenIds({
+      accounts: {
+        authority: owner.publicKey,
+        roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+        bridge
+      },
+      signers: [owner]
+    });
+    console.log("tx->", tx);
+  });
+
+  it("move legacy routes into token route accounts", async() => {
+    const bridgeData = await program.account.bridge.fetch(bridge);
+    // routes already moved out of the legacy table, by mint
+    const migrated = new Map<string, PublicKey[]>();
+
+    for (const [index, legacyTokenId] of bridgeData.tokenIds.entries()) {
+      const tokenId = Buffer.from(legacyTokenId, "hex");
+      const tokenMint: PublicKey = bridgeData.tokenAddresses[index];
+      const [bridgeTokenAccount] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("BRIDGE_TOKEN_VAULT_SEED"),
+          tokenMint.toBuffer()
+        ],
+        program.programId
+      );
+      const vaultAccount = await provider.connection.getAccountInfo(bridgeTokenAccount);
+      const otherRoutes = migrated.get(tokenMint.toBase58()) ?? [];
+
+      // the legacy table never tracked balances, the first route of a mint takes the whole
+      // vault balance here, split it between the routes by what each one owes in your product
+      const targetBalance = vaultAccount && otherRoutes.length == 0
+        ? new anchor.BN((await getAccount(provider.connection, bridgeTokenAccount)).amount.toString())
+        : new anchor.BN(0);
+
+      const tx = await program.rpc.migrateTokenRoute(
+        [...tokenId],
+        18, // remote token decimals
+        targetBalance,
+        {
+          accounts: {
+            authority: owner.publicKey,
+            roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+            bridge,
+            tokenMint,
+            tokenRoute: getTokenRoute(tokenId),
+            bridgeTokenAccount: vaultAccount ? bridgeTokenAccount : null,
+            tokenProgram: TOKEN_PROGRAM_ID,
+            systemProgram: SystemProgram.programId
+          },
+          // every route of the mint migrated so far, so the vault balance is not assigned twice
+          remainingAccounts: otherRoutes.map((pubkey) => ({ pubkey, isWritable: false, isSigner: false })),
+          signers: [owner]
+        }
+      );
+      console.log("tx->", tx);
+
+      const tokenRoute = await program.account.tokenRoute.fetch(getTokenRoute(tokenId));
+      assert.ok(tokenRoute.targetBalance.eq(targetBalance));
+      migrated.set(tokenMint.toBase58(), [...otherRoutes, getTokenRoute(tokenId)]);
+    }
+  });
+
+  it("revoke role", async() => {
+    const member = Keypair.generate().publicKey;
+    const roleMember = getRoleMember({ pauser: {} }, member);
+
+    console.log("tx->", await program.rpc.grantRole({ pauser: {} }, member, {
+      accounts: { owner: owner.publicKey, bridge, roleMember, systemProgram: SystemProgram.programId },
+      signers: [owner]
+    }));
+    console.log("tx->", await program.rpc.revokeRole({ pauser: {} }, member, {
+      accounts: { owner: owner.publicKey, bridge, roleMember },
+      signers: [owner]
+    }));
+  });
+
+  it("set protocol fee", async() => {
+    const protocolFee = 10000000; // 0.01 Sol, charged while no price feed is set
+    const protocolFeeUsdCents = 50; // 0.5 USD, charged at the price feed's SOL/USD price
+    const nonce = await scheduleOperation(
+      { setProtocolFee: { protocolFee: new anchor.BN(protocolFee), protocolFeeUsdCents: new anchor.BN(protocolFeeUsdCents) } },
+      { feeManager: {} }
+    );
+    const queuedOperation = await program.account.queuedOperation.fetch(getQueuedOperation(nonce));
+    console.log("queued operation->", queuedOperation);
+
+    // executable by anyone once the bridge timelock delay has passed
+    await expectTimelockNotExpired(program.rpc.setProtocolFee(
+     nonce,
+     {
+       accounts: {
+         bridge,
+         queuedOperation: getQueuedOperation(nonce),
+         scheduledBy: owner.publicKey,
+       }
+     }
+    ));
+  });
+  
+  it("set price feed", async() => {
+    const maxPriceAge = 10 * 365 * 24 * 60 * 60; // the mock price account has a fixed publish time
+    const maxPriceConfBps = 100;
+    const nonce = await scheduleOperation(
+      { setPriceFeed: { priceFeed, maxPriceAge: new anchor.BN(maxPriceAge), maxPriceConfBps } },
+      { feeManager: {} }
+    );
+
+    // fee configuration is timelocked like the protocol fee itself
+    await expectTimelockNotExpired(program.rpc.setPriceFeed(
+      nonce,
       {
         accounts: {
           bridge,
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+        }
+      }
+    ));
+  });
+
+  it("set guardians", async() => {
+    // the owner alone only sets the first guardian set, later changes are covered by "rotate guardians"
+    if ((await program.account.bridge.fetch(bridge)).guardians.length > 0) {
+      return;
+    }
+    const tx = await program.rpc.setGuardians(
+      guardians.map((guardian) => guardian.publicKey),
+      guardianThreshold,
+      {
+        accounts: {
           owner: owner.publicKey,
-          vault,
-          systemProgram: SystemProgram.programId
+          bridge,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
         },
         signers: [owner]
       }
@@ -94,21 +496,39 @@ describe("sol_bridge", () => {
     console.log("tx->", tx);
   });
 
-  it("set protocol fee", async() => {
-    const protocolFee = 10000000; // 0.01 Sol
-    const tx = await program.rpc.setProtocolFee(
-     new anchor.BN(protocolFee),
-     {
-       accounts: {
-         owner: owner.publicKey,
-         bridge,
-       },
-       signers: [owner]
-     }
-    );
+  it("rotate guardians", async() => {
+    const newGuardians = guardians.map((guardian) => guardian.publicKey);
+    const { guardianSetIndex } = await program.account.bridge.fetch(bridge);
+    const setGuardians = (preInstructions: TransactionInstruction[]) =>
+      program.rpc.setGuardians(
+        newGuardians,
+        guardianThreshold,
+        {
+          accounts: {
+            owner: owner.publicKey,
+            bridge,
+            instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+          },
+          preInstructions,
+          signers: [owner]
+        }
+      );
+
+    // the owner key alone cannot replace the guardian set
+    await expectInsufficientGuardianSignatures(setGuardians([]));
+
+    const digest = guardianSetDigest(guardianSetIndex, newGuardians, guardianThreshold);
+    const signatures = guardianSignatures(digest, guardians.slice(0, guardianThreshold));
+    const tx = await setGuardians(signatures);
     console.log("tx->", tx);
+
+    const bridgeData = await program.account.bridge.fetch(bridge);
+    assert.equal(bridgeData.guardianSetIndex, guardianSetIndex + 1);
+
+    // the approval was bound to the previous guardian set index
+    await expectInsufficientGuardianSignatures(setGuardians(signatures));
   });
-  
+
   it("add bridgeable token to the bridge", async() => {
     let bridgeData = await program.account.bridge.fetch(bridge);
     const remoteChainSelector = 56;
@@ -124,10 +544,17 @@ describe("sol_bridge", () => {
         const tx = await program.rpc.addToken(
           localToken, 
           new anchor.BN(remoteChainSelector),
-          remoteToken, {
+          evmAddress(remoteToken),
+          { lockRelease: {} },
+          18, {
             accounts: {
-              owner: owner.publicKey,
-              bridge
+              authority: owner.publicKey,
+              roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+              bridge,
+              tokenMint: localToken,
+              tokenRoute: getTokenRoute(getTokenId(localToken, remoteChainSelector, remoteToken)),
+              solEscrow: null,
+              systemProgram: SystemProgram.programId
             },
             signers: [owner]
           }
@@ -159,10 +586,17 @@ describe("sol_bridge", () => {
         const tx = await program.rpc.addToken(
           localToken, 
           new anchor.BN(remoteChainSelector),
-          remoteToken, {
+          evmAddress(remoteToken),
+          { lockRelease: {} },
+          18, {
             accounts: {
-              owner: owner.publicKey,
-              bridge
+              authority: owner.publicKey,
+              roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+              bridge,
+              tokenMint: localToken,
+              tokenRoute: getTokenRoute(getTokenId(localToken, remoteChainSelector, remoteToken)),
+              solEscrow: null,
+              systemProgram: SystemProgram.programId
             },
             signers: [owner]
           }
@@ -178,54 +612,125 @@ describe("sol_bridge", () => {
     }
   });
 
+  it("create wrapped token for a remote token", async() => {
+    const remoteChainSelector = 1;
+    const remoteToken = "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599"; // eth wbtc address
+    const tokenMetadataProgram = new PublicKey("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+    const [tokenMint] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("WRAPPED_MINT_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+        Buffer.from(evmAddress(remoteToken))
+      ],
+      program.programId
+    );
+    const [metadata] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("metadata"),
+        tokenMetadataProgram.toBuffer(),
+        tokenMint.toBuffer()
+      ],
+      tokenMetadataProgram
+    );
+
+    const tx = await program.rpc.createWrappedToken(
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      8, // remote token decimals
+      "Wrapped BTC (Ethereum)",
+      "WBTC",
+      "https://etherscan.io/token/" + remoteToken,
+      {
+        accounts: {
+          authority: owner.publicKey,
+          roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+          bridge,
+          tokenMint,
+          metadata,
+          tokenRoute: getTokenRoute(getTokenId(tokenMint, remoteChainSelector, remoteToken)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          tokenMetadataProgram,
+          systemProgram: SystemProgram.programId,
+          rent: SYSVAR_RENT_PUBKEY
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
+  });
 
   it("remove bridgeable token from the bridge", async() => {
-    let bridgeData = await program.account.bridge.fetch(bridge);
-    console.log("tokens->", bridgeData);
-    try {
-      const remoteChainSelector = 1;
-      const localToken = new PublicKey("5hyJ6h3ABjF7zEBhc32LWT5ZUCkNx4AZkdRzKC1MUHRb");
-      const remoteToken = "0xdac17f958d2ee523a2206206994597c13d831ec7"; //eth usdt address
-  
-      let listenerId: number;
-      const event = await new Promise<Event[E]>(async (res) => {
-        listenerId = program.addEventListener("RemoveTokenEvent", (event) => {
-          res(event);
-        });
-        const tx = await program.rpc.removeToken(
-          localToken,
-          new anchor.BN(remoteChainSelector),
-          remoteToken, 
-          {
-            accounts: {
-              owner: owner.publicKey,
-              bridge
-            },
-            signers: [owner]
-          }
-        );
-        console.log("tx->", tx);
-        bridgeData = await program.account.bridge.fetch(bridge);
-        console.log("tokens->", bridgeData.tokens);
-      });
-      await program.removeEventListener(listenerId);
-      console.log(event);
-    } catch (error) {
-      console.log(error);
-    }
+    const remoteChainSelector = 1;
+    const localToken = new PublicKey("5hyJ6h3ABjF7zEBhc32LWT5ZUCkNx4AZkdRzKC1MUHRb");
+    const remoteToken = "0xdac17f958d2ee523a2206206994597c13d831ec7"; //eth usdt address
+
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+    const nonce = await scheduleOperation(
+      { removeToken: { tokenId: [...tokenId] } },
+      { tokenRegistrar: {} }
+    );
+    await expectTimelockNotExpired(program.rpc.removeToken(
+      [...tokenId],
+      nonce,
+      {
+        accounts: {
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+          tokenRoute: getTokenRoute(tokenId)
+        }
+      }
+    ));
+  });
+
+  it("set liquidity fee", async() => {
+    const remoteChainSelector = 56;
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+
+    const sendFeeBps = 30;
+    const receiveFeeBps = 30;
+
+    const nonce = await scheduleOperation(
+      { setLpFee: { tokenId: [...tokenId], sendFeeBps, receiveFeeBps } },
+      { feeManager: {} }
+    );
+
+    await expectTimelockNotExpired(program.rpc.setLpFee(
+      [...tokenId],
+      nonce,
+      {
+        accounts: {
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+          tokenRoute: getTokenRoute(tokenId)
+        }
+      }
+    ));
   });
 
-  it("add liquidity by owner", async() => {
+  it("add liquidity by a liquidity provider", async() => {
 
     const remoteChainSelector = 56;
     const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
     const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
 
     const amount = 100000000;
 
     const tokenAccount = await getAssociatedTokenAddress(
       localToken,
-      owner.publicKey
+      user.publicKey
+    );
+
+    const [lpPosition] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("LP_POSITION_SEED"),
+        tokenId,
+        user.publicKey.toBuffer()
+      ],
+      program.programId
     );
 
     const [bridgeTokenAccount, _] = await anchor.web3.PublicKey.findProgramAddress(
@@ -244,22 +749,26 @@ describe("sol_bridge", () => {
         const tx = await program.rpc.addLiquidity(
           new anchor.BN(amount), 
           new anchor.BN(remoteChainSelector),
-          remoteToken, {
+          evmAddress(remoteToken), {
             accounts: {
-              user: owner.publicKey,
+              user: user.publicKey,
               bridge,
-              localToken,
+              tokenMint: localToken,
               tokenAccount,
+              tokenRoute: getTokenRoute(tokenId),
               bridgeTokenAccount,
+              lpMint: getLpMint(tokenId),
+              lpTokenAccount: getAssociatedTokenAddressSync(getLpMint(tokenId), user.publicKey),
+              lpPosition,
               tokenProgram: TOKEN_PROGRAM_ID,
+              associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
               systemProgram: SystemProgram.programId
             },
-            signers: [owner]
+            signers: [user]
           }
         );
         console.log("tx->", tx);
-        const bridgeData = await program.account.bridge.fetch(bridge);
-        console.log("tokens->", bridgeData);
+        console.log("route->", await program.account.tokenRoute.fetch(getTokenRoute(tokenId)));
       });
       await program.removeEventListener(listenerId);
       console.log(event);
@@ -268,14 +777,61 @@ describe("sol_bridge", () => {
     }
   });
 
-  it("update target token's balance", async() => {
-    let bridgeData = await program.account.bridge.fetch(bridge);
-    console.log("tokens->", bridgeData.tokens);
-
+  it("remove liquidity by a liquidity provider", async() => {
     const remoteChainSelector = 56;
     const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
     const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
-    const tokenAmount = 1000000000;
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+
+    const [bridgeTokenAccount] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("BRIDGE_TOKEN_VAULT_SEED"),
+        localToken.toBuffer()
+      ],
+      program.programId
+    );
+    const lpMint = getLpMint(tokenId);
+    const lpTokenAccount = getAssociatedTokenAddressSync(lpMint, user.publicKey);
+
+    // burn half of the provider's LP tokens, the rest keeps earning for "claim liquidity fees"
+    const shares = (await getAccount(provider.connection, lpTokenAccount)).amount / BigInt(2);
+    const routeBefore = await program.account.tokenRoute.fetch(getTokenRoute(tokenId));
+
+    const tx = await program.rpc.removeLiquidity(
+      new anchor.BN(shares.toString()),
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      {
+        accounts: {
+          user: user.publicKey,
+          bridge,
+          tokenMint: localToken,
+          tokenAccount: getAssociatedTokenAddressSync(localToken, user.publicKey),
+          tokenRoute: getTokenRoute(tokenId),
+          bridgeTokenAccount,
+          lpMint,
+          lpTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID
+        },
+        signers: [user]
+      }
+    );
+    console.log("tx->", tx);
+
+    // the burned shares leave the route, whose share count mirrors the LP mint supply
+    const routeAfter = await program.account.tokenRoute.fetch(getTokenRoute(tokenId));
+    assert.ok(routeBefore.lpShares.sub(routeAfter.lpShares).eq(new anchor.BN(shares.toString())));
+    assert.equal((await getMint(provider.connection, lpMint)).supply.toString(), routeAfter.lpShares.toString());
+  });
+
+  it("update target token's balance", async() => {
+    let bridgeData = await program.account.bridge.fetch(bridge);
+    console.log("tokens->", bridgeData.tokens);
+
+    const remoteChainSelector = 56;
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenAmount = 1000000000;
 
     try {
       const tx = await program.rpc.updateTokenBalance(
@@ -300,12 +856,130 @@ describe("sol_bridge", () => {
     }
   });
 
+  it("set fee schedule", async() => {
+    const remoteChainSelector = 56;
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+
+    const params = {
+      flatFee: new anchor.BN(1000000), // 0.001 Sol
+      feeBps: 10,
+      minFee: new anchor.BN(1000),
+      maxFee: new anchor.BN(100000000),
+      exempt: false
+    };
+    const nonce = await scheduleOperation(
+      { setFeeSchedule: { tokenId: [...tokenId], remoteChainSelector: new anchor.BN(remoteChainSelector), params } },
+      { feeManager: {} }
+    );
+
+    await expectTimelockNotExpired(program.rpc.setFeeSchedule(
+      [...tokenId],
+      new anchor.BN(remoteChainSelector),
+      nonce,
+      {
+        accounts: {
+          payer: owner.publicKey,
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+          tokenRoute: getTokenRoute(tokenId),
+          feeSchedule: getFeeSchedule(tokenId, remoteChainSelector),
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      }
+    ));
+  });
+
+  let minAmountOut = new anchor.BN(0); // remote amount quoted by quoteSend
+
+  // the owner doubles as the integrator referring sends in these tests
+  const [referral] = PublicKey.findProgramAddressSync(
+    [
+      Buffer.from("REFERRAL_SEED"),
+      owner.publicKey.toBuffer()
+    ],
+    program.programId
+  );
+  const referrerBps = 1000; // 10% of the lamport fee
+
+  it("set max referrer fee", async() => {
+    const tx = await program.rpc.setMaxReferrerBps(
+      2000,
+      {
+        accounts: {
+          authority: owner.publicKey,
+          roleMember: getRoleMember({ feeManager: {} }, owner.publicKey),
+          bridge,
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
+  });
+
+  it("register referrer", async() => {
+    if (await program.account.referral.fetchNullable(referral)) {
+      return;
+    }
+    const tx = await program.rpc.registerReferrer({
+      accounts: {
+        referrer: owner.publicKey,
+        referral,
+        systemProgram: SystemProgram.programId
+      },
+      signers: [owner]
+    });
+    console.log("tx->", tx);
+  });
+
+  it("quote send", async() => {
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const remoteBridge = "brigeaddress0x2394290389082395234"; // test value, modify this value in product
+    const remoteChainSelector = 56;
+    const sendAmount = 10000000;
+    const maxFeeLamports = 10000000; // 0.01 Sol, send fails if the fee is raised above it
+    const recipient = Buffer.from("8894E0a0c962CB723c1976a4421c95949bE2D4E3", "hex"); // test value, EVM address receiving the tokens
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+
+    // quote_send writes its result to the return data, read it by simulating the transaction
+    const quote = await program.methods
+      .quoteSend(
+        new anchor.BN(sendAmount),
+        remoteBridge,
+        new anchor.BN(remoteChainSelector),
+        evmAddress(remoteToken),
+        [...recipient],
+        new anchor.BN(maxFeeLamports),
+        new anchor.BN(0),
+        referrerBps
+      )
+      .accounts({
+        bridge,
+        tokenMint: localToken,
+        tokenRoute: getTokenRoute(tokenId),
+        feeSchedule: getFeeSchedule(tokenId, remoteChainSelector),
+        priceFeed,
+        referral
+      })
+      .view();
+    console.log("quote->", quote);
+
+    // the referrer's part of the lamport fee, paid by send out of the same fee
+    assert.ok(quote.referralFee.eq(quote.lamportFee.muln(referrerBps).divn(10000)));
+    minAmountOut = quote.remoteAmount;
+  });
+
   it("send tokens to the bridge", async() => {
     const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
     const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
     const remoteBridge = "brigeaddress0x2394290389082395234"; // test value, modify this value in product
     const remoteChainSelector = 56;
     const sendAmount = 10000000;
+    const maxFeeLamports = 10000000; // 0.01 Sol, send fails if the fee is raised above it
+    const recipient = Buffer.from("8894E0a0c962CB723c1976a4421c95949bE2D4E3", "hex"); // test value, EVM address receiving the tokens
 
     const tokenAccount = await getAssociatedTokenAddress(
       localToken,
@@ -320,11 +994,37 @@ describe("sol_bridge", () => {
       program.programId
     );
 
+    const [outboundSequence] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from("OUTBOUND_SEQUENCE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    );
+    const sequenceData = await program.account.outboundSequence.fetchNullable(outboundSequence);
+    const nonce = sequenceData ? sequenceData.sequence : new anchor.BN(0);
+
+    const [outboundMessage] = await anchor.web3.PublicKey.findProgramAddress(
+      [
+        Buffer.from("OUTBOUND_MESSAGE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+        nonce.toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    );
+
+    const tokenRoute = getTokenRoute(getTokenId(localToken, remoteChainSelector, remoteToken));
+    const targetBalanceBefore = (await program.account.tokenRoute.fetch(tokenRoute)).targetBalance;
+
     const tx = await program.rpc.send(
       new anchor.BN(sendAmount),
       remoteBridge,
       new anchor.BN(remoteChainSelector),
-      remoteToken,
+      evmAddress(remoteToken),
+      [...recipient],
+      new anchor.BN(maxFeeLamports),
+      new anchor.BN(minAmountOut),
+      referrerBps,
       {
       accounts: {
         user: user.publicKey,
@@ -332,64 +1032,442 @@ describe("sol_bridge", () => {
         vault,
         tokenMint: localToken,
         tokenAccount,
+        tokenRoute,
         bridgeTokenAccount,
+        feeSchedule: getFeeSchedule(getTokenId(localToken, remoteChainSelector, remoteToken), remoteChainSelector),
+        feeTokenAccount: getFeeTokenAccount(localToken),
+        priceFeed,
+        referral,
+        outboundSequence,
+        outboundMessage,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       },
       signers: [user]
     });
     console.log("tx->", tx);
+    const message = await program.account.outboundMessage.fetch(outboundMessage);
+    console.log("outbound message->", message);
+
+    // the route owes the locked amount back once the remote side sends it home
+    const targetBalanceAfter = (await program.account.tokenRoute.fetch(tokenRoute)).targetBalance;
+    assert.ok(targetBalanceAfter.sub(targetBalanceBefore).eq(message.amount));
+  });
+
+  it("send native sol to the bridge", async() => {
+    const remoteToken = "0x7130d2a12b9bcbfae4f2634d864a1ee1ce3ead9c"; // bsc wrapped sol address, test value
+    const remoteBridge = "brigeaddress0x2394290389082395234"; // test value, modify this value in product
+    const remoteChainSelector = 56;
+    const sendAmount = 100000000; // 0.1 Sol
+    const maxFeeLamports = 10000000; // 0.01 Sol, send fails if the fee is raised above it
+    const recipient = Buffer.from("8894E0a0c962CB723c1976a4421c95949bE2D4E3", "hex"); // test value, EVM address receiving the tokens
+    const tokenRoute = getTokenRoute(getTokenId(NATIVE_MINT, remoteChainSelector, remoteToken));
+    const [solEscrow] = PublicKey.findProgramAddressSync([Buffer.from("SOL_ESCROW_SEED")], program.programId);
+
+    if (!(await program.account.tokenRoute.fetchNullable(tokenRoute))) {
+      const tx = await program.rpc.addToken(
+        NATIVE_MINT,
+        new anchor.BN(remoteChainSelector),
+        evmAddress(remoteToken),
+        { native: {} },
+        18, {
+          accounts: {
+            authority: owner.publicKey,
+            roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+            bridge,
+            tokenMint: NATIVE_MINT,
+            tokenRoute,
+            solEscrow,
+            systemProgram: SystemProgram.programId
+          },
+          signers: [owner]
+        }
+      );
+      console.log("tx->", tx);
+    }
+
+    // adding the route funded the escrow's rent-exempt minimum, which stays out of the route balance
+    const rentExemptMinimum = await provider.connection.getMinimumBalanceForRentExemption(0);
+    assert.ok(await provider.connection.getBalance(solEscrow) >= rentExemptMinimum);
+    const [outboundSequence] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("OUTBOUND_SEQUENCE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    );
+    const sequenceData = await program.account.outboundSequence.fetchNullable(outboundSequence);
+    const [outboundMessage] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("OUTBOUND_MESSAGE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+        (sequenceData ? sequenceData.sequence : new anchor.BN(0)).toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    );
+
+    const tx = await program.rpc.send(
+      new anchor.BN(sendAmount),
+      remoteBridge,
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      [...recipient],
+      new anchor.BN(maxFeeLamports),
+      new anchor.BN(0),
+      0, // no referrer
+      {
+        accounts: {
+          user: user.publicKey,
+          bridge,
+          vault,
+          tokenMint: NATIVE_MINT,
+          tokenAccount: null,
+          tokenRoute,
+          solEscrow,
+          bridgeTokenAccount: null,
+          feeSchedule: getFeeSchedule(getTokenId(NATIVE_MINT, remoteChainSelector, remoteToken), remoteChainSelector),
+          feeTokenAccount: null,
+          priceFeed,
+          referral: null,
+          outboundSequence,
+          outboundMessage,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [user]
+      }
+    );
+    console.log("tx->", tx);
+  });
+
+  // creates a Token-2022 mint with `extensions`, initialized by `initExtensions` before the mint itself
+  const createToken2022Mint = async (
+    extensions: ExtensionType[],
+    initExtensions: (mint: PublicKey) => TransactionInstruction[]
+  ) => {
+    const mint = Keypair.generate();
+    const decimals = 9;
+    const mintLen = getMintLen(extensions);
+    const tx = new Transaction().add(
+      SystemProgram.createAccount({
+        fromPubkey: owner.publicKey,
+        newAccountPubkey: mint.publicKey,
+        space: mintLen,
+        lamports: await provider.connection.getMinimumBalanceForRentExemption(mintLen),
+        programId: TOKEN_2022_PROGRAM_ID
+      }),
+      ...initExtensions(mint.publicKey),
+      createInitializeMintInstruction(mint.publicKey, decimals, owner.publicKey, null, TOKEN_2022_PROGRAM_ID)
+    );
+    console.log("tx->", await sendAndConfirmTransaction(provider.connection, tx, [owner, mint]));
+    return mint.publicKey;
+  };
+
+  it("send a Token-2022 mint with a transfer fee", async() => {
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // test value, remote side of the route
+    const remoteBridge = "brigeaddress0x2394290389082395234"; // test value, modify this value in product
+    const remoteChainSelector = 56;
+    const feeBasisPoints = 100; // 1% withheld by the mint on every transfer
+    const maxFee = BigInt(1000000000);
+    const liquidityAmount = 100000000000;
+    const sendAmount = 10000000000;
+    const maxFeeLamports = 10000000; // 0.01 Sol, send fails if the fee is raised above it
+    const recipient = Buffer.from("8894E0a0c962CB723c1976a4421c95949bE2D4E3", "hex"); // test value, EVM address receiving the tokens
+
+    const localToken = await createToken2022Mint(
+      [ExtensionType.TransferFeeConfig],
+      (mint) => [
+        createInitializeTransferFeeConfigInstruction(mint, owner.publicKey, owner.publicKey, feeBasisPoints, maxFee, TOKEN_2022_PROGRAM_ID)
+      ]
+    );
+    const tokenAccount = await createAssociatedTokenAccount(provider.connection, owner, localToken, user.publicKey, undefined, TOKEN_2022_PROGRAM_ID);
+    await mintTo(provider.connection, owner, localToken, tokenAccount, owner, liquidityAmount + sendAmount, [], undefined, TOKEN_2022_PROGRAM_ID);
+
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+    const tokenRoute = getTokenRoute(tokenId);
+    console.log("tx->", await program.rpc.addToken(
+      localToken,
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      { lockRelease: {} },
+      18, {
+        accounts: {
+          authority: owner.publicKey,
+          roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+          bridge,
+          tokenMint: localToken,
+          tokenRoute,
+          solEscrow: null,
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      }
+    ));
+
+    // the first deposit opens the bridge vault of the mint
+    const [bridgeTokenAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from("BRIDGE_TOKEN_VAULT_SEED"), localToken.toBuffer()],
+      program.programId
+    );
+    const [lpPosition] = PublicKey.findProgramAddressSync(
+      [Buffer.from("LP_POSITION_SEED"), tokenId, user.publicKey.toBuffer()],
+      program.programId
+    );
+    console.log("tx->", await program.rpc.addLiquidity(
+      new anchor.BN(liquidityAmount),
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken), {
+        accounts: {
+          user: user.publicKey,
+          bridge,
+          tokenMint: localToken,
+          tokenAccount,
+          tokenRoute,
+          bridgeTokenAccount,
+          lpPosition,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId
+        },
+        signers: [user]
+      }
+    ));
+
+    const quote = await program.methods
+      .quoteSend(
+        new anchor.BN(sendAmount),
+        remoteBridge,
+        new anchor.BN(remoteChainSelector),
+        evmAddress(remoteToken),
+        [...recipient],
+        new anchor.BN(maxFeeLamports),
+        new anchor.BN(0),
+        0
+      )
+      .accounts({
+        bridge,
+        tokenMint: localToken,
+        tokenRoute,
+        feeSchedule: getFeeSchedule(tokenId, remoteChainSelector),
+        priceFeed,
+        referral: null
+      })
+      .view();
+    console.log("quote->", quote);
+    assert.ok(quote.transferFee.gtn(0));
+
+    const [outboundSequence] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("OUTBOUND_SEQUENCE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    );
+    const sequenceData = await program.account.outboundSequence.fetchNullable(outboundSequence);
+    const [outboundMessage] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("OUTBOUND_MESSAGE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+        (sequenceData ? sequenceData.sequence : new anchor.BN(0)).toArrayLike(Buffer, "be", 8)
+      ],
+      program.programId
+    );
+
+    const vaultBefore = (await getAccount(provider.connection, bridgeTokenAccount, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    const targetBalanceBefore = (await program.account.tokenRoute.fetch(tokenRoute)).targetBalance;
+
+    const tx = await program.rpc.send(
+      new anchor.BN(sendAmount),
+      remoteBridge,
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      [...recipient],
+      new anchor.BN(maxFeeLamports),
+      quote.remoteAmount,
+      0, // no referrer
+      {
+        accounts: {
+          user: user.publicKey,
+          bridge,
+          vault,
+          tokenMint: localToken,
+          tokenAccount,
+          tokenRoute,
+          solEscrow: null,
+          bridgeTokenAccount,
+          feeSchedule: getFeeSchedule(tokenId, remoteChainSelector),
+          feeTokenAccount: null,
+          priceFeed,
+          referral: null,
+          outboundSequence,
+          outboundMessage,
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [user]
+      }
+    );
+    console.log("tx->", tx);
+    const message = await program.account.outboundMessage.fetch(outboundMessage);
+
+    // the route is credited with what the vault actually received, not what the sender sent
+    const vaultAfter = (await getAccount(provider.connection, bridgeTokenAccount, undefined, TOKEN_2022_PROGRAM_ID)).amount;
+    const targetBalanceAfter = (await program.account.tokenRoute.fetch(tokenRoute)).targetBalance;
+    const received = new anchor.BN((vaultAfter - vaultBefore).toString());
+    assert.ok(received.eq(new anchor.BN(sendAmount).sub(quote.transferFee)));
+    assert.ok(targetBalanceAfter.sub(targetBalanceBefore).eq(received));
+    assert.ok(message.amount.eq(received));
+  });
+
+  it("reject a Token-2022 mint with a permanent delegate", async() => {
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // test value, remote side of the route
+    const remoteChainSelector = 56;
+
+    // a permanent delegate could move the tokens held in the bridge vault
+    const localToken = await createToken2022Mint(
+      [ExtensionType.PermanentDelegate],
+      (mint) => [createInitializePermanentDelegateInstruction(mint, owner.publicKey, TOKEN_2022_PROGRAM_ID)]
+    );
+
+    await assert.rejects(
+      program.rpc.addToken(
+        localToken,
+        new anchor.BN(remoteChainSelector),
+        evmAddress(remoteToken),
+        { lockRelease: {} },
+        18, {
+          accounts: {
+            authority: owner.publicKey,
+            roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+            bridge,
+            tokenMint: localToken,
+            tokenRoute: getTokenRoute(getTokenId(localToken, remoteChainSelector, remoteToken)),
+            solEscrow: null,
+            systemProgram: SystemProgram.programId
+          },
+          signers: [owner]
+        }
+      ),
+      (error: any) => error.error.errorCode.code === "UnsupportedMintExtension"
+    );
+  });
+
+  it("reject messages without enough valid guardian signatures", async() => {
+    const localToken = new PublicKey("5SUDTjKUQ6RBZ5nED3VcMCtUKAFhmJ4b5Ar4Yodpn7au");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const remoteChainSelector = 56;
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+    const remoteAmount = new anchor.BN("10000000000000000000");
+    const messageId = Buffer.from(keccak_256("rejected test message"));
+    const digest = messageDigest(remoteChainSelector, messageId, tokenId, user.publicKey, remoteAmount);
+    const receive = (signers: Keypair[], signedDigest = digest) =>
+      receiveMessage(localToken, remoteChainSelector, tokenId, messageId, remoteAmount, guardianSignatures(signedDigest, signers));
+
+    // fewer signatures than the threshold
+    await expectInsufficientGuardianSignatures(receive(guardians.slice(0, guardianThreshold - 1)));
+
+    // a signer outside the guardian set does not count
+    await expectInsufficientGuardianSignatures(receive([guardians[0], Keypair.generate()]));
+
+    // the same guardian signing twice counts once
+    await expectInsufficientGuardianSignatures(receive([guardians[0], guardians[0]]));
+
+    // signatures over another message, here a larger amount
+    const otherDigest = messageDigest(remoteChainSelector, messageId, tokenId, user.publicKey, remoteAmount.muln(2));
+    await expectInsufficientGuardianSignatures(receive(guardians.slice(0, guardianThreshold), otherDigest));
   });
 
   it("message receive", async() => {
     const localToken = new PublicKey("5SUDTjKUQ6RBZ5nED3VcMCtUKAFhmJ4b5Ar4Yodpn7au");
 
-    const tokenId = '64373931313736393231353231316439646438656234356161643466316333626166616434316362393765356231373039373365646162366336666535376537';
-    const remoteChainSelector = Number('b8159170038f96fb');
-    const sendAmount = 10000000;
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const remoteChainSelector = 56;
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+    const remoteAmount = new anchor.BN("10000000000000000000"); // 10 usdt in the remote token's 18 decimals
+    // keccak256(source tx hash, log index) of the EVM send, test value made unique per run so reruns deliver a new message
+    const messageId = Buffer.from(keccak_256(`0x5c504ed432cb51138bcf09aa5e8a410dd4a1e204ef84bfed1be16dfba1b22060:${Date.now()}`));
 
-    const tokenAccount = await getAssociatedTokenAddress(
-      localToken,
-      user.publicKey
+    const digest = messageDigest(remoteChainSelector, messageId, tokenId, user.publicKey, remoteAmount);
+    const signatures = guardianSignatures(digest, guardians.slice(0, guardianThreshold));
+    const targetBalanceBefore = (await program.account.tokenRoute.fetch(getTokenRoute(tokenId))).targetBalance;
+
+    const tx = await receiveMessage(localToken, remoteChainSelector, tokenId, messageId, remoteAmount, signatures);
+    console.log("tx->", tx);
+
+    const received = await getEvent(tx, "MessageReceivedEvent");
+    assert.ok(Buffer.from(received.messageId).equals(messageId));
+    assert.ok(received.toAddress.equals(user.publicKey));
+    assert.ok(received.remoteAmount.eq(remoteAmount));
+
+    // only the released amount leaves the route, the liquidity fee is added to its providers' liquidity
+    const targetBalanceAfter = (await program.account.tokenRoute.fetch(getTokenRoute(tokenId))).targetBalance;
+    assert.ok(targetBalanceBefore.sub(targetBalanceAfter).eq(received.amount));
+
+    // the same message, with valid signatures, is delivered only once
+    await assert.rejects(
+      receiveMessage(localToken, remoteChainSelector, tokenId, messageId, remoteAmount, signatures),
+      (error: any) => error.error.errorCode.code === "MessageAlreadyProcessed"
     );
+  });
 
-    const [bridgeTokenAccount, _] = await anchor.web3.PublicKey.findProgramAddress(
+  it("claim liquidity fees", async() => {
+    const remoteChainSelector = 56;
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+
+    const [bridgeTokenAccount] = PublicKey.findProgramAddressSync(
       [
         Buffer.from("BRIDGE_TOKEN_VAULT_SEED"),
         localToken.toBuffer()
       ],
       program.programId
     );
+    const [lpPosition] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("LP_POSITION_SEED"),
+        tokenId,
+        user.publicKey.toBuffer()
+      ],
+      program.programId
+    );
 
     try {
-      let listenerId: number;
-      const event = await new Promise<Event[E]>(async (res) => {
-        listenerId = program.addEventListener("MessageReceivedEvent", (event) => {
-          res(event);
-        });
-        const tx = await program.rpc.messageReceive(
-          tokenId,
-          new anchor.BN(remoteChainSelector),
-          new anchor.BN(sendAmount),
-          {
-            accounts: {
-              owner: owner.publicKey,
-              bridge,
-              tokenMint: localToken,
-              user: user.publicKey,
-              userTokenAccount:tokenAccount,
-              bridgeTokenAccount,
-              associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-              tokenProgram: TOKEN_PROGRAM_ID,
-              systemProgram: SystemProgram.programId
-            },
-            signers:[owner]
-          }
-        );
-        console.log("tx->", tx);
+      const tx = await program.rpc.claimLpFees(
+        new anchor.BN(remoteChainSelector),
+        evmAddress(remoteToken),
+        {
+          accounts: {
+            user: user.publicKey,
+            bridge,
+            tokenMint: localToken,
+            tokenAccount: getAssociatedTokenAddressSync(localToken, user.publicKey),
+            tokenRoute: getTokenRoute(tokenId),
+            bridgeTokenAccount,
+            lpMint: getLpMint(tokenId),
+            lpTokenAccount: getAssociatedTokenAddressSync(getLpMint(tokenId), user.publicKey),
+            lpPosition,
+            tokenProgram: TOKEN_PROGRAM_ID
+          },
+          signers: [user]
+        }
+      );
+      console.log("tx->", tx);
+      console.log("position->", await program.account.lpPosition.fetch(lpPosition));
+    } catch (error) {
+      console.log(error);
+    }
+  });
+
+  it("claim referral fees", async() => {
+    try {
+      const tx = await program.rpc.claimReferralFees({
+        accounts: {
+          referrer: owner.publicKey,
+          referral
+        },
+        signers: [owner]
       });
-      await program.removeEventListener(listenerId);
-      console.log(event);
+      console.log("tx->", tx);
     } catch (error) {
       console.log(error);
     }
@@ -398,7 +1476,8 @@ describe("sol_bridge", () => {
   it("withdraw Token", async() => {
     const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
 
-    const tokenId = '62363130373235323438643362363237633363386366386236666634616637663939646436353736376165316537663630653161626361653263363132643565';
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenId = getTokenId(localToken, 56, remoteToken);
     const withdrawAmount = 10000000;
 
     const tokenAccount = await getAssociatedTokenAddress(
@@ -414,64 +1493,250 @@ describe("sol_bridge", () => {
       program.programId
     );
 
-    try {
-      let listenerId: number;
-      const event = await new Promise<Event[E]>(async (res) => {
-        listenerId = program.addEventListener("WithdrawTokenEvent", (event) => {
-          res(event);
-        });
-        const tx = await program.rpc.withdrawToken(
-          tokenId,
-          new anchor.BN(withdrawAmount),{
-            accounts: {
-              bridge,
-              owner: owner.publicKey,
-              tokenMint: localToken,
-              bridgeTokenAccount,
-              beneficiaryTokenAccount: tokenAccount,
-              tokenProgram: TOKEN_PROGRAM_ID,
-              associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-              systemProgram: SystemProgram.programId
-            },
-            signers: [owner]
-          }
-        );
-        console.log("tx->", tx);
-      });
-      await program.removeEventListener(listenerId);
-      console.log(event);
-    } catch (error) {
-      console.log(error);
-    }
+    const nonce = await scheduleOperation(
+      {
+        withdrawToken: {
+          tokenId: [...tokenId],
+          beneficiary: owner.publicKey,
+          amount: new anchor.BN(withdrawAmount)
+        }
+      },
+      { treasurer: {} }
+    );
+    await expectTimelockNotExpired(program.rpc.withdrawToken(
+      [...tokenId],
+      nonce,{
+        accounts: {
+          bridge,
+          payer: user.publicKey,
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+          tokenRoute: getTokenRoute(tokenId),
+          tokenMint: localToken,
+          bridgeTokenAccount,
+          beneficiary: owner.publicKey,
+          beneficiaryTokenAccount: tokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId
+        },
+        signers: [user]
+      }
+    ));
   });
   
+  it("withdraw token fees", async() => {
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const feeTokenAccount = getFeeTokenAccount(localToken);
+    const amount = (await getAccount(provider.connection, feeTokenAccount)).amount;
+
+    const tx = await program.rpc.withdrawTokenFees(
+      new anchor.BN(amount.toString()),
+      {
+        accounts: {
+          bridge,
+          authority: owner.publicKey,
+          roleMember: getRoleMember({ treasurer: {} }, owner.publicKey),
+          tokenMint: localToken,
+          feeTokenAccount,
+          beneficiaryTokenAccount: getAssociatedTokenAddressSync(localToken, owner.publicKey),
+          tokenProgram: TOKEN_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
+  });
+
   it("withdraw fee sol", async() => {
     const withdrawAmount = 100000;
 
-    try {
-      let listenerId: number;
-      const event = await new Promise<Event[E]>(async (res) => {
-        listenerId = program.addEventListener("WithdrawEvent", (event) => {
-          res(event);
-        });
-        const tx = await program.rpc.withdraw(
-          new anchor.BN(withdrawAmount),{
-            accounts: {
-              owner: owner.publicKey,
-              bridge,
-              vault,
-              beneficiary: owner.publicKey,
-              systemProgram: SystemProgram.programId
-            },
-            signers: [owner]
-          }
-        );
-        console.log("tx->", tx);
-      });
-      await program.removeEventListener(listenerId);
-      console.log(event);
-    } catch (error) {
-      console.log(error);
-    }
+    const nonce = await scheduleOperation(
+      { withdraw: { beneficiary: owner.publicKey, amount: new anchor.BN(withdrawAmount) } },
+      { treasurer: {} }
+    );
+    await expectTimelockNotExpired(program.rpc.withdraw(
+      nonce,{
+        accounts: {
+          bridge,
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+          vault,
+          beneficiary: owner.publicKey,
+          systemProgram: SystemProgram.programId
+        }
+      }
+    ));
+  });
+
+  // test fee recipients, replace with the treasury, relayer and integrator accounts in your product
+  const feeRecipients = [
+    { recipient: Keypair.generate().publicKey, weight: 70 },
+    { recipient: Keypair.generate().publicKey, weight: 20 },
+    { recipient: Keypair.generate().publicKey, weight: 10 },
+  ];
+
+  it("set fee recipients", async() => {
+    const tx = await program.rpc.setFeeRecipients(
+      feeRecipients,
+      {
+        accounts: {
+          authority: owner.publicKey,
+          roleMember: getRoleMember({ feeManager: {} }, owner.publicKey),
+          bridge,
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
+  });
+
+  it("distribute fees", async() => {
+    // permissionless, any payer can trigger the distribution
+    const tx = await program.methods
+      .distributeFees()
+      .accounts({
+        bridge,
+        vault,
+        systemProgram: SystemProgram.programId
+      })
+      .remainingAccounts(feeRecipients.map(({ recipient }) => ({ pubkey: recipient, isWritable: true, isSigner: false })))
+      .rpc();
+    console.log("tx->", tx);
+  });
+
+  it("pause and unpause the bridge", async() => {
+    // the pauser role can stop the bridge, only the owner can resume it
+    console.log("tx->", await program.rpc.pause({
+      accounts: {
+        authority: owner.publicKey,
+        roleMember: getRoleMember({ pauser: {} }, owner.publicKey),
+        bridge,
+      },
+      signers: [owner]
+    }));
+    console.log("tx->", await program.rpc.unpause({
+      accounts: {
+        owner: owner.publicKey,
+        bridge,
+      },
+      signers: [owner]
+    }));
+  });
+
+  it("pause and unpause a route", async() => {
+    const remoteChainSelector = 56;
+    const localToken = new PublicKey("8NtheYSKWDkCgWoc8HScQFkcCTF1FiFEbbriosZLNmtE");
+    const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+    const tokenId = getTokenId(localToken, remoteChainSelector, remoteToken);
+
+    // pause outbound sends only, message_receive keeps working
+    console.log("tx->", await program.rpc.pauseRoute([...tokenId], true, false, {
+      accounts: {
+        authority: owner.publicKey,
+        roleMember: getRoleMember({ pauser: {} }, owner.publicKey),
+        tokenRoute: getTokenRoute(tokenId),
+      },
+      signers: [owner]
+    }));
+    console.log("tx->", await program.rpc.unpauseRoute([...tokenId], true, false, {
+      accounts: {
+        owner: owner.publicKey,
+        bridge,
+        tokenRoute: getTokenRoute(tokenId),
+      },
+      signers: [owner]
+    }));
+  });
+
+  const proposeOwner = async (currentOwner: Keypair, pendingOwner: PublicKey) =>
+    await program.rpc.proposeOwner(
+      pendingOwner,
+      {
+        accounts: {
+          owner: currentOwner.publicKey,
+          bridge,
+        },
+        signers: [currentOwner]
+      }
+    );
+
+  const acceptOwner = async (pendingOwner: Keypair) =>
+    await program.rpc.acceptOwner({
+      accounts: {
+        pendingOwner: pendingOwner.publicKey,
+        bridge,
+      },
+      signers: [pendingOwner]
+    });
+
+  it("propose owner and renounce the proposal", async() => {
+    console.log("tx->", await proposeOwner(owner, user.publicKey));
+
+    // the pending owner turns the proposal down, the owner can cancel it the same way
+    const tx = await program.rpc.cancelOwnerProposal({
+      accounts: {
+        signer: user.publicKey,
+        bridge,
+      },
+      signers: [user]
+    });
+    console.log("tx->", tx);
+  });
+
+  it("transfer ownership and back", async() => {
+    console.log("tx->", await proposeOwner(owner, user.publicKey));
+    console.log("tx->", await acceptOwner(user));
+
+    console.log("tx->", await proposeOwner(user, owner.publicKey));
+    console.log("tx->", await acceptOwner(owner));
+
+    const bridgeData = await program.account.bridge.fetch(bridge);
+    console.log("owner->", bridgeData.owner.toBase58());
+  });
+
+  it("guardian cancels a scheduled operation", async() => {
+    const nonce = await scheduleOperation(
+      { withdraw: { beneficiary: user.publicKey, amount: new anchor.BN(100000) } },
+      { treasurer: {} }
+    );
+
+    const tx = await program.rpc.cancelOperation(
+      nonce,
+      {
+        accounts: {
+          guardian: guardians[0].publicKey,
+          bridge,
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+        },
+        signers: [guardians[0]]
+      }
+    );
+    console.log("tx->", tx);
+  });
+
+  it("set timelock delay", async() => {
+    const timelockDelay = 2 * 24 * 60 * 60; // 2 days, operations scheduled from now on wait this long
+
+    // below the one day minimum, the delay is rejected
+    await assert.rejects(
+      program.rpc.setTimelockDelay(new anchor.BN(60), { accounts: { owner: owner.publicKey, bridge }, signers: [owner] }),
+      (error: any) => error.error.errorCode.code === "InvalidTimelockDelay"
+    );
+
+    const tx = await program.rpc.setTimelockDelay(
+      new anchor.BN(timelockDelay),
+      {
+        accounts: {
+          owner: owner.publicKey,
+          bridge,
+        },
+        signers: [owner]
+      }
+    );
+    console.log("tx->", tx);
   });
 });
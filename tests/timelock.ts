@@ -0,0 +1,470 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { IDL, SolBridge } from "../target/types/sol_bridge";
+
+import { TOKEN_PROGRAM_ID, ASSOCIATED_TOKEN_PROGRAM_ID, MINT_SIZE, createInitializeMintInstruction, createAssociatedTokenAccountInstruction, createMintToCheckedInstruction, getAssociatedTokenAddressSync, unpackAccount } from "@solana/spl-token";
+import { SystemProgram, Keypair, PublicKey, Transaction, LAMPORTS_PER_SOL, SYSVAR_INSTRUCTIONS_PUBKEY } from "@solana/web3.js";
+import { keccak_256 } from "@noble/hashes/sha3";
+import { BankrunProvider } from "anchor-bankrun";
+import { Clock, ProgramTestContext, start } from "solana-bankrun";
+import assert from "assert";
+import fs from "fs";
+
+// Queued operations only run a day after they are scheduled, so these tests run the
+// program in bankrun and move its clock past each operation's eta
+describe("sol_bridge timelock", () => {
+  const programId = new PublicKey("6gUrEYhacs6ZeHZFfDEBih1PRY7417vTYZjbfD62mkjV");
+  const loader = new PublicKey("BPFLoaderUpgradeab1e11111111111111111111111");
+  const programData = PublicKey.findProgramAddressSync([programId.toBuffer()], loader)[0];
+
+  const owner = Keypair.generate();
+  const localToken = Keypair.generate();
+  const guardian = Keypair.generate();
+
+  const chainSelector = 1601511254; // test value
+  const protocolFee = 10000000; // 0.01 Sol
+  const remoteChainSelector = 56;
+  const remoteToken = "0x55d398326f99059fF775485246999027B3197955"; // bsc usdt address
+  const decimals = 6; // the same on both sides, so no amount is dust
+
+  let context: ProgramTestContext;
+  let provider: BankrunProvider;
+  let program: Program<SolBridge>;
+
+  const [bridge] = PublicKey.findProgramAddressSync([Buffer.from("BRIDGE_SEED")], programId);
+  const [vault] = PublicKey.findProgramAddressSync([Buffer.from("VAULT_SEED")], programId);
+  const [bridgeTokenAccount] = PublicKey.findProgramAddressSync(
+    [Buffer.from("BRIDGE_TOKEN_VAULT_SEED"), localToken.publicKey.toBuffer()],
+    programId
+  );
+  const tokenAccount = getAssociatedTokenAddressSync(localToken.publicKey, owner.publicKey);
+
+  const evmAddress = (address: string) => [...Buffer.from(address.slice(2), "hex")];
+
+  // keccak256(abi.encodePacked(uint64 chainSelector, bytes32 localToken, uint64 remoteChainSelector, address remoteToken))
+  const tokenId = Buffer.from(keccak_256(Buffer.concat([
+    new anchor.BN(chainSelector).toArrayLike(Buffer, "be", 8),
+    localToken.publicKey.toBuffer(),
+    new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+    Buffer.from(evmAddress(remoteToken)),
+  ])));
+  const [tokenRoute] = PublicKey.findProgramAddressSync([Buffer.from("TOKEN_ROUTE_SEED"), tokenId], programId);
+  const [lpMint] = PublicKey.findProgramAddressSync([Buffer.from("LP_MINT_SEED"), tokenId], programId);
+  const lpTokenAccount = getAssociatedTokenAddressSync(lpMint, owner.publicKey);
+  const [lpPosition] = PublicKey.findProgramAddressSync(
+    [Buffer.from("LP_POSITION_SEED"), tokenId, owner.publicKey.toBuffer()],
+    programId
+  );
+
+  const roles = ["relayer", "feeManager", "tokenRegistrar", "pauser", "treasurer"];
+  const getRoleMember = (role: object, member: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("ROLE_SEED"), Buffer.from([roles.indexOf(Object.keys(role)[0])]), member.toBuffer()],
+      programId
+    )[0];
+
+  const getQueuedOperation = (nonce: anchor.BN) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("QUEUED_OPERATION_SEED"), nonce.toArrayLike(Buffer, "be", 8)],
+      programId
+    )[0];
+
+  const expectError = (execute: Promise<string>, name: string) =>
+    assert.rejects(execute, (error: any) => error.code === program.idl.errors.find((e) => e.name === name).code);
+
+  const tokenBalance = async (address: PublicKey) =>
+    unpackAccount(address, await provider.connection.getAccountInfo(address)).amount;
+
+  const scheduleOperation = async (operation: object, role: object) => {
+    const nonce = (await program.account.bridge.fetch(bridge)).operationNonce;
+    await program.rpc.scheduleOperation(operation, {
+      accounts: {
+        authority: owner.publicKey,
+        roleMember: getRoleMember(role, owner.publicKey),
+        bridge,
+        queuedOperation: getQueuedOperation(nonce),
+        systemProgram: SystemProgram.programId
+      },
+      signers: [owner]
+    });
+    return nonce;
+  };
+
+  // moves the bank clock to the operation's eta, the first second it can run
+  const warpToEta = async (nonce: anchor.BN) => {
+    const { eta } = await program.account.queuedOperation.fetch(getQueuedOperation(nonce));
+    const clock = await context.banksClient.getClock();
+    context.setClock(new Clock(clock.slot, clock.epochStartTimestamp, clock.epoch, clock.leaderScheduleEpoch, BigInt(eta.toString())));
+  };
+
+  const expectClosed = async (address: PublicKey) =>
+    assert.equal(await context.banksClient.getAccount(address), null);
+
+  const send = async (amount: number) => {
+    const [outboundSequence] = PublicKey.findProgramAddressSync(
+      [Buffer.from("OUTBOUND_SEQUENCE_SEED"), new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8)],
+      programId
+    );
+    const sequence = (await program.account.outboundSequence.fetchNullable(outboundSequence))?.sequence ?? new anchor.BN(0);
+    const [outboundMessage] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("OUTBOUND_MESSAGE_SEED"),
+        new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8),
+        sequence.toArrayLike(Buffer, "be", 8)
+      ],
+      programId
+    );
+    const [feeSchedule] = PublicKey.findProgramAddressSync(
+      [Buffer.from("FEE_SCHEDULE_SEED"), tokenId, new anchor.BN(remoteChainSelector).toArrayLike(Buffer, "be", 8)],
+      programId
+    );
+
+    await program.rpc.send(
+      new anchor.BN(amount),
+      "brigeaddress0x2394290389082395234", // test value
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      evmAddress("0x000000000000000000000000000000000000dEaD"),
+      (await program.account.bridge.fetch(bridge)).protocolFee,
+      new anchor.BN(0),
+      0, // no referrer
+      {
+        accounts: {
+          user: owner.publicKey,
+          bridge,
+          vault,
+          tokenMint: localToken.publicKey,
+          tokenAccount,
+          tokenRoute,
+          solEscrow: null,
+          bridgeTokenAccount,
+          feeSchedule,
+          feeTokenAccount: null,
+          priceFeed: null,
+          referral: null,
+          outboundSequence,
+          outboundMessage,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          systemProgram: SystemProgram.programId,
+        },
+        signers: [owner]
+      }
+    );
+  };
+
+  const addToken = () =>
+    program.rpc.addToken(
+      localToken.publicKey,
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      { lockRelease: {} },
+      decimals,
+      {
+        accounts: {
+          authority: owner.publicKey,
+          roleMember: getRoleMember({ tokenRegistrar: {} }, owner.publicKey),
+          bridge,
+          tokenMint: localToken.publicKey,
+          tokenRoute,
+          solEscrow: null,
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      }
+    );
+
+  const addLiquidity = (amount: number) =>
+    program.rpc.addLiquidity(new anchor.BN(amount), new anchor.BN(remoteChainSelector), evmAddress(remoteToken), {
+      accounts: {
+        user: owner.publicKey,
+        bridge,
+        tokenMint: localToken.publicKey,
+        tokenAccount,
+        tokenRoute,
+        bridgeTokenAccount,
+        lpMint,
+        lpTokenAccount,
+        lpPosition,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId
+      },
+      signers: [owner]
+    });
+
+  const removeLiquidity = async (shares: bigint) =>
+    program.rpc.removeLiquidity(
+      new anchor.BN(shares.toString()),
+      new anchor.BN(remoteChainSelector),
+      evmAddress(remoteToken),
+      {
+        accounts: {
+          user: owner.publicKey,
+          bridge,
+          tokenMint: localToken.publicKey,
+          tokenAccount,
+          tokenRoute,
+          bridgeTokenAccount,
+          lpMint,
+          lpTokenAccount,
+          tokenProgram: TOKEN_PROGRAM_ID
+        },
+        signers: [owner]
+      }
+    );
+
+  // schedules a withdrawal of the route's tokens that are not provider liquidity and runs it at its eta
+  const withdrawToken = async () => {
+    const route = await program.account.tokenRoute.fetch(tokenRoute);
+    const amount = route.targetBalance.sub(route.liquidity);
+    const nonce = await scheduleOperation(
+      { withdrawToken: { tokenId: [...tokenId], beneficiary: owner.publicKey, amount } },
+      { treasurer: {} }
+    );
+    await warpToEta(nonce);
+
+    await program.rpc.withdrawToken([...tokenId], nonce, {
+      accounts: {
+        bridge,
+        payer: owner.publicKey,
+        queuedOperation: getQueuedOperation(nonce),
+        scheduledBy: owner.publicKey,
+        tokenRoute,
+        tokenMint: localToken.publicKey,
+        bridgeTokenAccount,
+        beneficiary: owner.publicKey,
+        beneficiaryTokenAccount: tokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId
+      },
+      signers: [owner]
+    });
+    return { amount, nonce };
+  };
+
+  const removeToken = async () => {
+    const nonce = await scheduleOperation({ removeToken: { tokenId: [...tokenId] } }, { tokenRegistrar: {} });
+    await warpToEta(nonce);
+
+    await program.rpc.removeToken([...tokenId], nonce, {
+      accounts: {
+        queuedOperation: getQueuedOperation(nonce),
+        scheduledBy: owner.publicKey,
+        tokenRoute,
+      }
+    });
+    return nonce;
+  };
+
+  before(async () => {
+    // deploy the program as an upgradeable program whose upgrade authority is the owner,
+    // which initialize checks through the ProgramData account
+    const elf = fs.readFileSync("target/deploy/sol_bridge.so");
+    const programAccount = Buffer.alloc(36);
+    programAccount.writeUInt32LE(2, 0);
+    programData.toBuffer().copy(programAccount, 4);
+    const programDataHeader = Buffer.alloc(45);
+    programDataHeader.writeUInt32LE(3, 0);
+    programDataHeader.writeUInt8(1, 12);
+    owner.publicKey.toBuffer().copy(programDataHeader, 13);
+
+    context = await start([], [
+      {
+        address: programId,
+        info: { lamports: LAMPORTS_PER_SOL, data: programAccount, owner: loader, executable: true },
+      },
+      {
+        address: programData,
+        info: { lamports: 100 * LAMPORTS_PER_SOL, data: Buffer.concat([programDataHeader, elf]), owner: loader, executable: false },
+      },
+      {
+        address: owner.publicKey,
+        info: { lamports: 1000 * LAMPORTS_PER_SOL, data: Buffer.alloc(0), owner: SystemProgram.programId, executable: false },
+      },
+    ]);
+    // programs are only visible from the slot after the one they were deployed in
+    context.warpToSlot(BigInt(2));
+
+    provider = new BankrunProvider(context);
+    program = new Program<SolBridge>(IDL, programId, provider);
+
+    await program.rpc.initialize(new anchor.BN(protocolFee), new anchor.BN(chainSelector), {
+      accounts: {
+        bridge,
+        owner: owner.publicKey,
+        vault,
+        program: programId,
+        programData,
+        systemProgram: SystemProgram.programId
+      },
+      signers: [owner]
+    });
+
+    for (const role of ["feeManager", "tokenRegistrar", "treasurer"]) {
+      await program.rpc.grantRole({ [role]: {} }, owner.publicKey, {
+        accounts: {
+          owner: owner.publicKey,
+          bridge,
+          roleMember: getRoleMember({ [role]: {} }, owner.publicKey),
+          systemProgram: SystemProgram.programId
+        },
+        signers: [owner]
+      });
+    }
+
+    await program.rpc.setGuardians([guardian.publicKey], 1, {
+      accounts: { owner: owner.publicKey, bridge, instructions: SYSVAR_INSTRUCTIONS_PUBKEY },
+      signers: [owner]
+    });
+
+    // a lock/release route for a fresh mint, the owner holds its supply
+    const rent = await context.banksClient.getRent();
+    await provider.sendAndConfirm(
+      new Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: owner.publicKey,
+          newAccountPubkey: localToken.publicKey,
+          space: MINT_SIZE,
+          lamports: Number(rent.minimumBalance(BigInt(MINT_SIZE))),
+          programId: TOKEN_PROGRAM_ID,
+        }),
+        createInitializeMintInstruction(localToken.publicKey, decimals, owner.publicKey, null),
+        createAssociatedTokenAccountInstruction(owner.publicKey, tokenAccount, owner.publicKey, localToken.publicKey),
+        createMintToCheckedInstruction(localToken.publicKey, tokenAccount, owner.publicKey, 1000000000000, decimals)
+      ),
+      [owner, localToken]
+    );
+
+    await addToken();
+  });
+
+  it("runs a queued operation from its eta and closes it", async () => {
+    const nonce = await scheduleOperation(
+      { setProtocolFee: { protocolFee: new anchor.BN(2 * protocolFee), protocolFeeUsdCents: new anchor.BN(0) } },
+      { feeManager: {} }
+    );
+    await warpToEta(nonce);
+
+    await program.rpc.setProtocolFee(nonce, {
+      accounts: {
+        bridge,
+        queuedOperation: getQueuedOperation(nonce),
+        scheduledBy: owner.publicKey,
+      }
+    });
+
+    assert.ok((await program.account.bridge.fetch(bridge)).protocolFee.eqn(2 * protocolFee));
+    // the rent of the queued operation goes back to whoever scheduled it
+    await expectClosed(getQueuedOperation(nonce));
+  });
+
+  it("rejects a withdraw executed for another beneficiary", async () => {
+    const amount = LAMPORTS_PER_SOL / 2;
+    await provider.sendAndConfirm(
+      new Transaction().add(SystemProgram.transfer({ fromPubkey: owner.publicKey, toPubkey: vault, lamports: LAMPORTS_PER_SOL })),
+      [owner]
+    );
+
+    const nonce = await scheduleOperation(
+      { withdraw: { beneficiary: owner.publicKey, amount: new anchor.BN(amount) } },
+      { treasurer: {} }
+    );
+    await warpToEta(nonce);
+
+    const withdraw = (beneficiary: PublicKey) =>
+      program.rpc.withdraw(nonce, {
+        accounts: {
+          bridge,
+          queuedOperation: getQueuedOperation(nonce),
+          scheduledBy: owner.publicKey,
+          vault,
+          beneficiary,
+          systemProgram: SystemProgram.programId
+        }
+      });
+
+    await expectError(withdraw(Keypair.generate().publicKey), "InvalidOperation");
+
+    const vaultBefore = (await provider.connection.getAccountInfo(vault)).lamports;
+    await withdraw(owner.publicKey);
+    assert.equal(vaultBefore - (await provider.connection.getAccountInfo(vault)).lamports, amount);
+    await expectClosed(getQueuedOperation(nonce));
+  });
+
+  it("withdraws the tokens locked for a route", async () => {
+    // provider liquidity opens the bridge vault and is not withdrawable by the treasurer
+    await addLiquidity(1000000);
+    await send(5000000);
+    const { liquidity } = await program.account.tokenRoute.fetch(tokenRoute);
+
+    const balanceBefore = await tokenBalance(tokenAccount);
+    const { amount, nonce } = await withdrawToken();
+
+    assert.equal((await tokenBalance(tokenAccount)) - balanceBefore, BigInt(amount.toString()));
+    assert.ok((await program.account.tokenRoute.fetch(tokenRoute)).targetBalance.eq(liquidity));
+    await expectClosed(getQueuedOperation(nonce));
+  });
+
+  it("removes a route once it is empty", async () => {
+    await removeLiquidity(await tokenBalance(lpTokenAccount));
+    const nonce = await removeToken();
+
+    await expectClosed(tokenRoute);
+    await expectClosed(getQueuedOperation(nonce));
+  });
+
+  it("removes a route after its provider claimed the liquidity fees and left", async () => {
+    // the route is registered again, its LP mint and the provider's position outlive it
+    await addToken();
+
+    const nonce = await scheduleOperation(
+      { setLpFee: { tokenId: [...tokenId], sendFeeBps: 100, receiveFeeBps: 100 } },
+      { feeManager: {} }
+    );
+    await warpToEta(nonce);
+    await program.rpc.setLpFee([...tokenId], nonce, {
+      accounts: {
+        queuedOperation: getQueuedOperation(nonce),
+        scheduledBy: owner.publicKey,
+        tokenRoute
+      }
+    });
+
+    // the send's liquidity fee is added to the provider's liquidity
+    await addLiquidity(10000000);
+    await send(5000000);
+    const route = await program.account.tokenRoute.fetch(tokenRoute);
+    assert.ok(route.liquidity.gtn(10000000));
+    await withdrawToken();
+
+    const balanceBefore = await tokenBalance(tokenAccount);
+    const sharesBefore = await tokenBalance(lpTokenAccount);
+    await program.rpc.claimLpFees(new anchor.BN(remoteChainSelector), evmAddress(remoteToken), {
+      accounts: {
+        user: owner.publicKey,
+        bridge,
+        tokenMint: localToken.publicKey,
+        tokenAccount,
+        tokenRoute,
+        bridgeTokenAccount,
+        lpMint,
+        lpTokenAccount,
+        lpPosition,
+        tokenProgram: TOKEN_PROGRAM_ID
+      },
+      signers: [owner]
+    });
+    // claiming burns the shares the fees are worth
+    assert.ok((await tokenBalance(tokenAccount)) > balanceBefore);
+    assert.ok((await tokenBalance(lpTokenAccount)) < sharesBefore);
+
+    // rounding in the claim leaves nothing behind once the last share is burned
+    await removeLiquidity(await tokenBalance(lpTokenAccount));
+    const emptied = await program.account.tokenRoute.fetch(tokenRoute);
+    assert.ok(emptied.targetBalance.isZero() && emptied.liquidity.isZero() && emptied.lpShares.isZero());
+
+    await removeToken();
+    await expectClosed(tokenRoute);
+  });
+});